@@ -0,0 +1,519 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::color::Color;
+
+/// Keyframed changes to the scene over a range of frames.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "AnimationConfig", into = "AnimationConfig")]
+pub struct Animation {
+    pub start: u32,
+    pub end: u32,
+    pub fps: f32,
+    pub tracks: Vec<Track>,
+}
+
+impl Animation {
+    pub fn frames(&self) -> impl Iterator<Item = u32> {
+        self.start..=self.end
+    }
+
+    /// Evaluates every track at `frame`, which may fall between whole frames.
+    pub fn sample(&self, frame: f32) -> impl Iterator<Item = (&Target, Vec3)> + '_ {
+        self.tracks
+            .iter()
+            .map(move |track| (&track.target, track.sample(frame)))
+    }
+}
+
+/// A single animated property. Keyframe values are stored as vectors: scalars use `x` and
+/// colors are linear RGB.
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub target: Target,
+    pub interpolation: Interpolation,
+    /// `(frame, value)` pairs sorted by frame.
+    pub keyframes: Vec<(f32, Vec3)>,
+}
+
+impl Track {
+    pub fn sample(&self, frame: f32) -> Vec3 {
+        let keys = &self.keyframes;
+        let next = keys.partition_point(|(time, _)| *time <= frame);
+
+        if next == 0 {
+            return keys[0].1;
+        }
+
+        if next == keys.len() {
+            return keys[next - 1].1;
+        }
+
+        let (t0, p0) = keys[next - 1];
+        let (t1, p1) = keys[next];
+        let s = (frame - t0) / (t1 - t0);
+
+        match self.interpolation {
+            Interpolation::Step => p0,
+            Interpolation::Linear => p0 + (p1 - p0) * s,
+            Interpolation::Bezier => {
+                let h = t1 - t0;
+                let m0 = self.tangent(next - 1);
+                let m1 = self.tangent(next);
+
+                let s2 = s * s;
+                let s3 = s2 * s;
+
+                p0 * (2.0 * s3 - 3.0 * s2 + 1.0)
+                    + m0 * (h * (s3 - 2.0 * s2 + s))
+                    + p1 * (-2.0 * s3 + 3.0 * s2)
+                    + m1 * (h * (s3 - s2))
+            }
+        }
+    }
+
+    /// Catmull-Rom slope at a keyframe, flat at both ends of the track so motion eases in
+    /// and out.
+    fn tangent(&self, index: usize) -> Vec3 {
+        let keys = &self.keyframes;
+
+        if index == 0 || index + 1 == keys.len() {
+            return Vec3::zero();
+        }
+
+        let (before, a) = keys[index - 1];
+        let (after, b) = keys[index + 1];
+        (b - a) / (after - before)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Smooth cubic curve through every keyframe.
+    Bezier,
+    /// Holds each value until the next keyframe.
+    Step,
+}
+
+/// An animatable property, written in the config as a path such as `camera.origin`,
+/// `entity[2].material.color` or `light[0].intensity`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Camera(CameraProperty),
+    Entity(usize, EntityProperty),
+    Light(usize, LightProperty),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraProperty {
+    Origin,
+    Yaw,
+    Pitch,
+    Fov,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntityProperty {
+    Position,
+    Radius,
+    Normal,
+    Color,
+    Albedo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightProperty {
+    Position,
+    Direction,
+    Color,
+    Intensity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Scalar,
+    Vector,
+    Color,
+}
+
+impl Target {
+    fn kind(&self) -> Kind {
+        match self {
+            Target::Camera(CameraProperty::Origin)
+            | Target::Entity(_, EntityProperty::Position | EntityProperty::Normal)
+            | Target::Light(_, LightProperty::Position | LightProperty::Direction) => Kind::Vector,
+            Target::Entity(_, EntityProperty::Color) | Target::Light(_, LightProperty::Color) => {
+                Kind::Color
+            }
+            _ => Kind::Scalar,
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Camera(property) => {
+                let property = match property {
+                    CameraProperty::Origin => "origin",
+                    CameraProperty::Yaw => "yaw",
+                    CameraProperty::Pitch => "pitch",
+                    CameraProperty::Fov => "fov",
+                };
+                write!(f, "camera.{property}")
+            }
+            Target::Entity(index, property) => {
+                let property = match property {
+                    EntityProperty::Position => "position",
+                    EntityProperty::Radius => "radius",
+                    EntityProperty::Normal => "normal",
+                    EntityProperty::Color => "material.color",
+                    EntityProperty::Albedo => "material.albedo",
+                };
+                write!(f, "entity[{index}].{property}")
+            }
+            Target::Light(index, property) => {
+                let property = match property {
+                    LightProperty::Position => "position",
+                    LightProperty::Direction => "direction",
+                    LightProperty::Color => "color",
+                    LightProperty::Intensity => "intensity",
+                };
+                write!(f, "light[{index}].{property}")
+            }
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let (object, property) = path
+            .split_once('.')
+            .ok_or_else(|| anyhow!("expected `object.property`"))?;
+
+        if object == "camera" {
+            let property = match property {
+                "origin" => CameraProperty::Origin,
+                "yaw" => CameraProperty::Yaw,
+                "pitch" => CameraProperty::Pitch,
+                "fov" => CameraProperty::Fov,
+                _ => bail!("unknown camera property `{property}`"),
+            };
+
+            return Ok(Target::Camera(property));
+        }
+
+        let (name, index) = object
+            .strip_suffix(']')
+            .and_then(|object| object.split_once('['))
+            .ok_or_else(|| anyhow!("unknown object `{object}`"))?;
+        let index = index
+            .parse()
+            .with_context(|| format!("invalid index in `{object}`"))?;
+
+        match name {
+            "entity" => {
+                let property = match property {
+                    "position" => EntityProperty::Position,
+                    "radius" => EntityProperty::Radius,
+                    "normal" => EntityProperty::Normal,
+                    "material.color" => EntityProperty::Color,
+                    "material.albedo" => EntityProperty::Albedo,
+                    _ => bail!("unknown entity property `{property}`"),
+                };
+
+                Ok(Target::Entity(index, property))
+            }
+            "light" => {
+                let property = match property {
+                    "position" => LightProperty::Position,
+                    "direction" => LightProperty::Direction,
+                    "color" => LightProperty::Color,
+                    "intensity" => LightProperty::Intensity,
+                    _ => bail!("unknown light property `{property}`"),
+                };
+
+                Ok(Target::Light(index, property))
+            }
+            _ => bail!("unknown object `{name}`"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct AnimationConfig {
+    #[serde(default)]
+    start: u32,
+    end: u32,
+    #[serde(default = "AnimationConfig::fps")]
+    fps: f32,
+    #[serde(default)]
+    tracks: Vec<TrackConfig>,
+}
+
+impl AnimationConfig {
+    fn fps() -> f32 {
+        24.0
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct TrackConfig {
+    target: String,
+    #[serde(default)]
+    interpolation: Interpolation,
+    keys: Vec<KeyframeConfig>,
+}
+
+/// A keyframe placed either on a `frame` or at a `time` in seconds.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct KeyframeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frame: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<f32>,
+    value: ValueConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ValueConfig {
+    Scalar(f32),
+    Vector([f32; 3]),
+}
+
+impl ValueConfig {
+    fn new(kind: Kind, value: Vec3) -> ValueConfig {
+        match kind {
+            Kind::Scalar => ValueConfig::Scalar(value.x),
+            Kind::Vector => ValueConfig::Vector(value.into()),
+            Kind::Color => {
+                let [r, g, b, _] = Color::from(<[f32; 3]>::from(value)).as_slice();
+                ValueConfig::Vector([r, g, b].map(f32::from))
+            }
+        }
+    }
+
+    fn resolve(self, kind: Kind) -> anyhow::Result<Vec3> {
+        Ok(match (kind, self) {
+            (Kind::Scalar, ValueConfig::Scalar(value)) => Vec3::new(value, 0.0, 0.0),
+            (Kind::Vector, ValueConfig::Vector(value)) => Vec3::from(value),
+            (Kind::Color, ValueConfig::Scalar(hex)) => {
+                Vec3::from(<[f32; 3]>::from(Color::from_hex(hex as u32)))
+            }
+            (Kind::Color, ValueConfig::Vector(rgb)) => {
+                let color = Color::from(rgb.map(|channel| channel as u8));
+                Vec3::from(<[f32; 3]>::from(color))
+            }
+            (Kind::Scalar, _) => bail!("expected a number"),
+            (Kind::Vector, _) => bail!("expected `[x, y, z]`"),
+        })
+    }
+}
+
+impl From<Animation> for AnimationConfig {
+    /// Writes keyframes on frames, with colors as sRGB bytes.
+    fn from(animation: Animation) -> Self {
+        let tracks = animation
+            .tracks
+            .into_iter()
+            .map(|track| {
+                let kind = track.target.kind();
+                let keys = track
+                    .keyframes
+                    .into_iter()
+                    .map(|(frame, value)| KeyframeConfig {
+                        frame: Some(frame),
+                        time: None,
+                        value: ValueConfig::new(kind, value),
+                    })
+                    .collect();
+
+                TrackConfig {
+                    target: track.target.to_string(),
+                    interpolation: track.interpolation,
+                    keys,
+                }
+            })
+            .collect();
+
+        AnimationConfig {
+            start: animation.start,
+            end: animation.end,
+            fps: animation.fps,
+            tracks,
+        }
+    }
+}
+
+impl TryFrom<AnimationConfig> for Animation {
+    type Error = anyhow::Error;
+
+    fn try_from(config: AnimationConfig) -> Result<Self, Self::Error> {
+        ensure!(
+            config.start <= config.end,
+            "animation ends before it starts"
+        );
+        ensure!(config.fps > 0.0, "animation fps must be positive");
+
+        let mut tracks = Vec::with_capacity(config.tracks.len());
+
+        for track in config.tracks {
+            let target: Target = track
+                .target
+                .parse()
+                .with_context(|| format!("invalid animation target `{}`", track.target))?;
+            ensure!(!track.keys.is_empty(), "`{}` has no keys", track.target);
+
+            let mut keyframes = Vec::with_capacity(track.keys.len());
+
+            for key in track.keys {
+                let frame = match (key.frame, key.time) {
+                    (Some(frame), None) => frame,
+                    (None, Some(time)) => time * config.fps,
+                    _ => bail!("`{}` keys need either a frame or a time", track.target),
+                };
+                let value = key
+                    .value
+                    .resolve(target.kind())
+                    .with_context(|| format!("invalid key for `{}`", track.target))?;
+
+                keyframes.push((frame, value));
+            }
+
+            keyframes.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            tracks.push(Track {
+                target,
+                interpolation: track.interpolation,
+                keyframes,
+            });
+        }
+
+        Ok(Animation {
+            start: config.start,
+            end: config.end,
+            fps: config.fps,
+            tracks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use super::{
+        Animation, CameraProperty, EntityProperty, Interpolation, LightProperty, Target, Track,
+    };
+
+    fn track(interpolation: Interpolation, keys: &[(f32, f32)]) -> Track {
+        Track {
+            target: Target::Camera(CameraProperty::Yaw),
+            interpolation,
+            keyframes: keys
+                .iter()
+                .map(|&(frame, value)| (frame, Vec3::new(value, 0.0, 0.0)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn values_hold_outside_the_keyframes() {
+        let track = track(Interpolation::Linear, &[(10.0, 1.0), (20.0, 3.0)]);
+
+        assert_eq!(track.sample(0.0).x, 1.0);
+        assert_eq!(track.sample(30.0).x, 3.0);
+    }
+
+    #[test]
+    fn interpolation_modes() {
+        let keys = [(0.0, 0.0), (10.0, 10.0), (20.0, 0.0)];
+
+        let linear = track(Interpolation::Linear, &keys);
+        assert_eq!(linear.sample(5.0).x, 5.0);
+        assert_eq!(linear.sample(15.0).x, 5.0);
+
+        let step = track(Interpolation::Step, &keys);
+        assert_eq!(step.sample(9.9).x, 0.0);
+        assert_eq!(step.sample(10.0).x, 10.0);
+
+        let bezier = track(Interpolation::Bezier, &keys);
+        assert_eq!(bezier.sample(10.0).x, 10.0);
+        // The flat tangent at the first key eases out of it more slowly than a straight line.
+        assert!(bezier.sample(2.0).x < linear.sample(2.0).x);
+        assert!((bezier.sample(5.0).x - 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn target_paths() {
+        assert_eq!(
+            "camera.origin".parse::<Target>().unwrap(),
+            Target::Camera(CameraProperty::Origin)
+        );
+        assert_eq!(
+            "entity[3].material.color".parse::<Target>().unwrap(),
+            Target::Entity(3, EntityProperty::Color)
+        );
+        assert_eq!(
+            "light[0].intensity".parse::<Target>().unwrap(),
+            Target::Light(0, LightProperty::Intensity)
+        );
+
+        assert!("camera.roll".parse::<Target>().is_err());
+        assert!("entity.position".parse::<Target>().is_err());
+        assert!("entity[x].position".parse::<Target>().is_err());
+
+        for path in [
+            "camera.fov",
+            "entity[1].material.albedo",
+            "light[2].direction",
+        ] {
+            assert_eq!(path.parse::<Target>().unwrap().to_string(), path);
+        }
+    }
+
+    #[test]
+    fn keys_resolve_by_target() {
+        let animation: Animation = serde_yaml::from_str(
+            "
+            end: 48
+            fps: 24
+            tracks:
+              - target: entity[0].material.color
+                keys:
+                  - { time: 2, value: 0xFF0000 }
+                  - { frame: 0, value: [0, 0, 255] }
+            ",
+        )
+        .unwrap();
+
+        let track = &animation.tracks[0];
+        assert_eq!(track.keyframes[0], (0.0, Vec3::new(0.0, 0.0, 1.0)));
+        assert_eq!(track.keyframes[1], (48.0, Vec3::new(1.0, 0.0, 0.0)));
+
+        let saved = serde_yaml::to_string(&animation).unwrap();
+        let reloaded: Animation = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.tracks[0].keyframes, track.keyframes);
+
+        let invalid = serde_yaml::from_str::<Animation>(
+            "
+            end: 10
+            tracks:
+              - target: camera.origin
+                keys:
+                  - { frame: 0, value: 1.0 }
+            ",
+        );
+        assert!(invalid.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::Deserialize;
+use ultraviolet::{Vec2, Vec3};
+
+use crate::color::Color;
+use crate::ray::Intersection;
+use crate::world::Shading;
+
+/// Arbitrary output variables: per-pixel data written next to the beauty image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aov {
+    /// Distance along the camera ray to the first hit.
+    Depth,
+    /// World space surface normal at the first hit.
+    Normal,
+    /// Surface color at the first hit, without lighting.
+    Albedo,
+    Uv,
+    EntityId,
+    MaterialId,
+    /// Light sources reflected straight off the first hit.
+    Direct,
+    /// Everything else: reflections, volumes and fog.
+    Indirect,
+    /// Light reflected off the first hit from the mirror direction.
+    Reflection,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 9] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Uv,
+        Aov::EntityId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Reflection,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::EntityId => "entity_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Reflection => "reflection",
+        }
+    }
+
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::EntityId | Aov::MaterialId => &["id"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::Reflection => &["R", "G", "B"],
+        }
+    }
+}
+
+/// Everything the AOVs need to know about a single camera ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovSample {
+    pub depth: f32,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub uv: Vec2,
+    /// Index of the entity that was hit, or `-1` for the sky.
+    pub entity_id: f32,
+    /// Identifier of the material that was hit, or `-1` for the sky.
+    pub material_id: f32,
+    pub direct: Color,
+    pub indirect: Color,
+    pub reflection: Color,
+}
+
+impl AovSample {
+    pub fn hit(intersection: &Intersection, shading: &Shading, beauty: Color) -> AovSample {
+        AovSample {
+            depth: intersection.t,
+            normal: intersection.contact.normal,
+            albedo: intersection.color(),
+            uv: intersection.contact.texture,
+            entity_id: intersection.entity as f32,
+            material_id: intersection.contact.material.id as f32,
+            direct: shading.direct,
+            indirect: beauty - shading.direct,
+            reflection: shading.reflection,
+        }
+    }
+
+    /// The sky counts as direct light and as the albedo of the background.
+    pub fn miss(sky: Color, beauty: Color) -> AovSample {
+        AovSample {
+            depth: f32::INFINITY,
+            normal: Vec3::zero(),
+            albedo: sky,
+            uv: Vec2::zero(),
+            entity_id: -1.0,
+            material_id: -1.0,
+            direct: sky,
+            indirect: beauty - sky,
+            reflection: Color::default(),
+        }
+    }
+
+    /// Moves this sample toward `other` by `weight`, keeping a running average of the samples
+    /// of a pixel like the beauty pass does. Ids cannot be averaged and keep the first
+    /// sample's, and depth stays infinite once any sample has seen the sky.
+    pub fn blend(self, other: AovSample, weight: f32) -> AovSample {
+        if weight >= 1.0 {
+            return other;
+        }
+
+        let depth = if self.depth.is_finite() && other.depth.is_finite() {
+            self.depth + (other.depth - self.depth) * weight
+        } else {
+            f32::INFINITY
+        };
+
+        AovSample {
+            depth,
+            normal: self.normal + (other.normal - self.normal) * weight,
+            albedo: Color::lerp(self.albedo, other.albedo, weight),
+            uv: self.uv + (other.uv - self.uv) * weight,
+            direct: Color::lerp(self.direct, other.direct, weight),
+            indirect: Color::lerp(self.indirect, other.indirect, weight),
+            reflection: Color::lerp(self.reflection, other.reflection, weight),
+            ..self
+        }
+    }
+
+    /// Values of `aov`, in the order of `Aov::channels`.
+    pub fn values(&self, aov: Aov) -> Vec<f32> {
+        match aov {
+            Aov::Depth => vec![self.depth],
+            Aov::Normal => self.normal.as_array().to_vec(),
+            Aov::Albedo => <[f32; 3]>::from(self.albedo).to_vec(),
+            Aov::Uv => self.uv.as_array().to_vec(),
+            Aov::EntityId => vec![self.entity_id],
+            Aov::MaterialId => vec![self.material_id],
+            Aov::Direct => <[f32; 3]>::from(self.direct).to_vec(),
+            Aov::Indirect => <[f32; 3]>::from(self.indirect).to_vec(),
+            Aov::Reflection => <[f32; 3]>::from(self.reflection).to_vec(),
+        }
+    }
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        AovSample::miss(Color::default(), Color::default())
+    }
+}
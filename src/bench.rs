@@ -0,0 +1,156 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use raytracer::stats::{self, Stats};
+use raytracer::{render_buffer, RenderSettings, Scene};
+
+/// Timings and ray counts of rendering one scene several times.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    scene: PathBuf,
+    width: u32,
+    height: u32,
+    samples: u32,
+    /// Seconds taken by each timed render.
+    runs: Vec<f64>,
+    seconds: Summary,
+    rays: Rays<u64>,
+    /// Rays of each kind traced per second of the median render.
+    rays_per_second: Rays<f64>,
+    intersection_tests: u64,
+    intersection_tests_per_ray: f64,
+    bvh_node_visits: u64,
+    bvh_node_visits_per_ray: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    min: f64,
+    median: f64,
+    mean: f64,
+    max: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Rays<T> {
+    primary: T,
+    shadow: T,
+    reflection: T,
+    total: T,
+}
+
+/// Renders `scene` once to warm up and then `runs` more times, timing each render. Every
+/// pixel seeds its own samples, so each run traces exactly the same rays.
+pub fn run(path: &Path, scene: &Scene, samples: u32, runs: u32) -> Report {
+    let settings = RenderSettings {
+        samples,
+        ..RenderSettings::default()
+    };
+
+    render_buffer(scene, &settings);
+    stats::take();
+
+    let mut times = Vec::new();
+    let mut counts = Stats::default();
+
+    for _ in 0..runs.max(1) {
+        let start = Instant::now();
+        render_buffer(scene, &settings);
+        times.push(start.elapsed().as_secs_f64());
+        counts = stats::take();
+    }
+
+    Report::new(path, scene, settings.samples.max(1), times, counts)
+}
+
+impl Report {
+    fn new(path: &Path, scene: &Scene, samples: u32, runs: Vec<f64>, counts: Stats) -> Report {
+        let mut sorted = runs.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        let seconds = Summary {
+            min: sorted[0],
+            median: sorted[sorted.len() / 2],
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            max: sorted[sorted.len() - 1],
+        };
+
+        let rays = Rays {
+            primary: counts.primary_rays,
+            shadow: counts.shadow_rays,
+            reflection: counts.reflection_rays,
+            total: counts.rays(),
+        };
+        let per_second = |count: u64| count as f64 / seconds.median;
+        let per_ray = |count: u64| count as f64 / counts.rays().max(1) as f64;
+
+        Report {
+            scene: path.to_path_buf(),
+            width: scene.width(),
+            height: scene.height(),
+            samples,
+            runs,
+            rays_per_second: Rays {
+                primary: per_second(rays.primary),
+                shadow: per_second(rays.shadow),
+                reflection: per_second(rays.reflection),
+                total: per_second(rays.total),
+            },
+            seconds,
+            rays,
+            intersection_tests: counts.intersection_tests,
+            intersection_tests_per_ray: per_ray(counts.intersection_tests),
+            bvh_node_visits: counts.bvh_node_visits,
+            bvh_node_visits_per_ray: per_ray(counts.bvh_node_visits),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Report {
+            seconds,
+            rays,
+            rays_per_second: rate,
+            ..
+        } = self;
+
+        writeln!(
+            f,
+            "{} at {}x{}, {} samples, {} runs",
+            self.scene.display(),
+            self.width,
+            self.height,
+            self.samples,
+            self.runs.len()
+        )?;
+        writeln!(
+            f,
+            "time      min {:.3}s  median {:.3}s  mean {:.3}s  max {:.3}s",
+            seconds.min, seconds.median, seconds.mean, seconds.max
+        )?;
+        writeln!(
+            f,
+            "rays      {} primary, {} shadow, {} reflection, {} total",
+            rays.primary, rays.shadow, rays.reflection, rays.total
+        )?;
+        writeln!(
+            f,
+            "rays/s    {:.0} primary, {:.0} shadow, {:.0} reflection, {:.0} total",
+            rate.primary, rate.shadow, rate.reflection, rate.total
+        )?;
+        writeln!(
+            f,
+            "tests     {} intersection tests, {:.2} per ray",
+            self.intersection_tests, self.intersection_tests_per_ray
+        )?;
+        writeln!(
+            f,
+            "bvh       {} node visits, {:.2} per ray",
+            self.bvh_node_visits, self.bvh_node_visits_per_ray
+        )
+    }
+}
@@ -0,0 +1,398 @@
+//! Bounding volume hierarchy, letting a ray skip every primitive whose box it misses.
+
+use std::ops::Range;
+
+use ultraviolet::Vec3;
+
+use crate::ray::Ray;
+use crate::stats;
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// A box containing nothing, which grows to fit whatever is added to it.
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::broadcast(f32::INFINITY),
+        max: Vec3::broadcast(f32::NEG_INFINITY),
+    };
+
+    pub fn of(points: impl IntoIterator<Item = Vec3>) -> Aabb {
+        points.into_iter().fold(Aabb::EMPTY, Aabb::grow)
+    }
+
+    pub fn grow(self, point: Vec3) -> Aabb {
+        Aabb {
+            min: self.min.min_by_component(point),
+            max: self.max.max_by_component(point),
+        }
+    }
+
+    pub fn union(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min_by_component(other.min),
+            max: self.max.max_by_component(other.max),
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Distance from `point` to the nearest point in the box, or zero inside it.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        (self.min - point)
+            .max_by_component(point - self.max)
+            .max_by_component(Vec3::zero())
+            .mag()
+    }
+
+    /// Distance along the ray at which it enters the box, if it does so within `range`.
+    /// `inverse` is the reciprocal of the ray direction.
+    fn entry(&self, origin: Vec3, inverse: Vec3, range: &Range<f32>) -> Option<f32> {
+        let a = (self.min - origin) * inverse;
+        let b = (self.max - origin) * inverse;
+        let (mut near, mut far) = (range.start, range.end);
+
+        for axis in 0..3 {
+            // A ray running along a face of the box gives 0 * inf, and stays level with the
+            // box on that axis.
+            if a[axis].is_nan() || b[axis].is_nan() {
+                continue;
+            }
+
+            near = near.max(a[axis].min(b[axis]));
+            far = far.min(a[axis].max(b[axis]));
+        }
+
+        (near <= far).then_some(near)
+    }
+}
+
+/// Primitives per leaf, beyond which a node is split in two.
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Primitive indices, ordered so that every leaf covers a contiguous run.
+    order: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Aabb,
+    /// First entry of `order` in a leaf, or the index of the second child otherwise. The
+    /// first child always follows its parent.
+    start: u32,
+    /// Number of primitives in a leaf, or zero for interior nodes.
+    count: u32,
+}
+
+impl Bvh {
+    /// Builds a hierarchy over primitives with the given bounds, splitting each node at the
+    /// median along its longest axis.
+    pub fn new(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() / LEAF_SIZE * 2 + 1),
+            order: (0..bounds.len() as u32).collect(),
+        };
+
+        if !bounds.is_empty() {
+            bvh.split(bounds, 0, bounds.len());
+        }
+
+        bvh
+    }
+
+    fn split(&mut self, bounds: &[Aabb], start: usize, end: usize) -> usize {
+        let index = self.nodes.len();
+        let primitives = &mut self.order[start..end];
+        let bounds_of = |primitive: &u32| bounds[*primitive as usize];
+
+        self.nodes.push(Node {
+            bounds: primitives
+                .iter()
+                .map(bounds_of)
+                .fold(Aabb::EMPTY, Aabb::union),
+            start: start as u32,
+            count: (end - start) as u32,
+        });
+
+        let centers = Aabb::of(primitives.iter().map(|p| bounds_of(p).center()));
+        let extent = centers.max - centers.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        // Primitives sharing a center cannot be told apart by splitting any further.
+        if end - start <= LEAF_SIZE || extent[axis] <= 0.0 {
+            return index;
+        }
+
+        let middle = (end - start) / 2;
+        primitives.select_nth_unstable_by(middle, |a, b| {
+            bounds_of(a).center()[axis].total_cmp(&bounds_of(b).center()[axis])
+        });
+
+        self.split(bounds, start, start + middle);
+        let second = self.split(bounds, start + middle, end);
+
+        let node = &mut self.nodes[index];
+        node.start = second as u32;
+        node.count = 0;
+        index
+    }
+
+    /// Finds the closest primitive along `ray` within `range`. `hit` tests the primitive with
+    /// the given index against the range left, returning how far along the ray it was hit and
+    /// whatever the caller needs to know about the hit.
+    pub fn closest<T>(
+        &self,
+        ray: &Ray,
+        mut range: Range<f32>,
+        mut hit: impl FnMut(usize, Range<f32>) -> Option<(f32, T)>,
+    ) -> Option<T> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse = Vec3::one() / ray.direction;
+        let entry = |node: usize, range: &Range<f32>| {
+            self.nodes[node].bounds.entry(ray.origin, inverse, range)
+        };
+
+        // Median splits keep the tree balanced, so its depth is far below the stack size.
+        let mut stack = [0; 64];
+        let mut len = 1;
+        let mut closest = None;
+        let mut visits = 0;
+        let mut tests = 0;
+
+        while len > 0 {
+            len -= 1;
+            let index = stack[len];
+
+            // The range may have shrunk since the node was pushed.
+            if entry(index, &range).is_none() {
+                continue;
+            }
+
+            visits += 1;
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                tests += node.count as u64;
+                let start = node.start as usize;
+                for &primitive in &self.order[start..start + node.count as usize] {
+                    if let Some((t, found)) = hit(primitive as usize, range.clone()) {
+                        range.end = t;
+                        closest = Some(found);
+                    }
+                }
+                continue;
+            }
+
+            let first = (index + 1, entry(index + 1, &range));
+            let second = (node.start as usize, entry(node.start as usize, &range));
+
+            // Visit the nearer child first so that it can rule out the other.
+            let children = if first.1 < second.1 {
+                [second, first]
+            } else {
+                [first, second]
+            };
+
+            for (child, distance) in children {
+                if distance.is_some() {
+                    stack[len] = child;
+                    len += 1;
+                }
+            }
+        }
+
+        stats::count(|stats| {
+            stats.bvh_node_visits += visits;
+            stats.intersection_tests += tests;
+        });
+        closest
+    }
+
+    /// Finds the primitive nearest to `point`. `distance` measures how far the primitive with
+    /// the given index is from the point, which must be no less than the distance to its
+    /// bounds, along with whatever the caller needs to know about it.
+    pub fn nearest<T>(
+        &self,
+        point: Vec3,
+        mut distance: impl FnMut(usize) -> (f32, T),
+    ) -> Option<T> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut stack = [0; 64];
+        let mut len = 1;
+        let mut best = f32::INFINITY;
+        let mut nearest = None;
+
+        while len > 0 {
+            len -= 1;
+            let index = stack[len];
+            let node = &self.nodes[index];
+
+            // A closer primitive may have been found since the node was pushed.
+            if node.bounds.distance(point) > best {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.start as usize;
+                for &primitive in &self.order[start..start + node.count as usize] {
+                    let (d, found) = distance(primitive as usize);
+                    if d < best {
+                        best = d;
+                        nearest = Some(found);
+                    }
+                }
+                continue;
+            }
+
+            let first = (index + 1, self.nodes[index + 1].bounds.distance(point));
+            let second = (
+                node.start as usize,
+                self.nodes[node.start as usize].bounds.distance(point),
+            );
+
+            // Visit the nearer child first so that it can rule out the other.
+            let children = if first.1 < second.1 {
+                [second, first]
+            } else {
+                [first, second]
+            };
+
+            for (child, d) in children {
+                if d <= best {
+                    stack[len] = child;
+                    len += 1;
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::ray::Ray;
+
+    use super::{Aabb, Bvh, LEAF_SIZE};
+
+    /// Unit cubes in a row along the z axis, one unit apart.
+    fn row(count: usize) -> Vec<Aabb> {
+        (0..count)
+            .map(|i| {
+                let min = Vec3::new(0.0, 0.0, 2.0 * i as f32);
+                Aabb::of([min, min + Vec3::one()])
+            })
+            .collect()
+    }
+
+    /// Index of the closest cube along `ray`, along with every cube tested to find it.
+    fn closest(bounds: &[Aabb], ray: &Ray) -> (Option<usize>, Vec<usize>) {
+        let inverse = Vec3::one() / ray.direction;
+        let mut tested = Vec::new();
+        let found = Bvh::new(bounds).closest(ray, Ray::UNBOUNDED, |index, range| {
+            tested.push(index);
+            let t = bounds[index].entry(ray.origin, inverse, &range)?;
+            Some((t, index))
+        });
+        (found, tested)
+    }
+
+    #[test]
+    fn finds_the_closest_hit_across_leaves() {
+        let bounds = row(16);
+
+        let forward = Ray::new(Vec3::new(0.5, 0.5, -5.0), Vec3::unit_z());
+        assert_eq!(closest(&bounds, &forward).0, Some(0));
+
+        let backward = Ray::new(Vec3::new(0.5, 0.5, 50.0), -Vec3::unit_z());
+        assert_eq!(closest(&bounds, &backward).0, Some(15));
+
+        let between = Ray::new(Vec3::new(0.5, 0.5, 13.5), Vec3::unit_z());
+        assert_eq!(closest(&bounds, &between).0, Some(7));
+
+        let past = Ray::new(Vec3::new(0.5, 0.5, -5.0), Vec3::unit_x());
+        assert_eq!(closest(&bounds, &past), (None, vec![]));
+    }
+
+    #[test]
+    fn skips_leaves_behind_the_closest_hit() {
+        let bounds = row(16);
+
+        let forward = Ray::new(Vec3::new(0.5, 0.5, -5.0), Vec3::unit_z());
+        let (found, tested) = closest(&bounds, &forward);
+        assert_eq!(found, Some(0));
+        assert_eq!(tested.len(), LEAF_SIZE);
+        assert!(tested.iter().all(|&index| index < LEAF_SIZE));
+
+        let backward = Ray::new(Vec3::new(0.5, 0.5, 50.0), -Vec3::unit_z());
+        let (found, tested) = closest(&bounds, &backward);
+        assert_eq!(found, Some(15));
+        assert_eq!(tested.len(), LEAF_SIZE);
+        assert!(tested.iter().all(|&index| index >= 16 - LEAF_SIZE));
+    }
+
+    #[test]
+    fn finds_the_nearest_primitive_to_a_point() {
+        let bounds = row(16);
+        let bvh = Bvh::new(&bounds);
+        let mut measured = 0;
+        let mut nearest = |point: Vec3| {
+            bvh.nearest(point, |index| {
+                measured += 1;
+                (bounds[index].distance(point), index)
+            })
+        };
+
+        assert_eq!(nearest(Vec3::new(0.5, 0.5, 14.5)), Some(7));
+        assert_eq!(nearest(Vec3::new(3.0, -2.0, 100.0)), Some(15));
+        assert_eq!(nearest(Vec3::new(0.5, 0.5, -3.0)), Some(0));
+        assert!(measured <= 3 * 2 * LEAF_SIZE);
+
+        assert_eq!(
+            Bvh::new(&[]).nearest(Vec3::zero(), |index| (0.0, index)),
+            None
+        );
+    }
+
+    #[test]
+    fn axis_parallel_rays_along_a_face_enter_the_box() {
+        let unit = Aabb::of([Vec3::zero(), Vec3::one()]);
+        let along = |origin: Vec3, direction: Vec3| {
+            unit.entry(origin, Vec3::one() / direction, &Ray::UNBOUNDED)
+        };
+
+        // On the face x = 0, where (0 - 0) * inf is NaN.
+        assert_eq!(along(Vec3::new(0.0, 0.5, -2.0), Vec3::unit_z()), Some(2.0));
+        // On the edge x = 1, y = 1.
+        assert_eq!(along(Vec3::new(1.0, 1.0, 3.0), -Vec3::unit_z()), Some(2.0));
+        // Level with the box on x, but beside it.
+        assert_eq!(along(Vec3::new(2.0, 0.5, -2.0), Vec3::unit_z()), None);
+        assert_eq!(along(Vec3::new(-1.0, 0.5, -2.0), Vec3::unit_z()), None);
+
+        let bounds = row(16);
+        let face = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::unit_z());
+        assert_eq!(closest(&bounds, &face).0, Some(0));
+    }
+}
@@ -1,8 +1,5 @@
 use crate::ray::Screen;
-use ultraviolet::{Vec2, Vec3};
-use winit::dpi::PhysicalSize;
-use winit::event::VirtualKeyCode;
-use winit_input_helper::WinitInputHelper;
+use ultraviolet::{Mat3, Vec2, Vec3};
 
 #[derive(Debug)]
 pub struct Camera {
@@ -10,36 +7,63 @@ pub struct Camera {
     pub viewport_width: f32,
     pub fov: f32,
     pub origin: Vec3,
+    /// Rotation around the vertical axis in radians, positive to the right.
+    pub yaw: f32,
+    /// Rotation around the horizontal axis in radians, positive upwards.
+    pub pitch: f32,
     pub speed: f32,
+    /// Displacement of the origin between shutter open and close.
+    pub motion: Vec3,
 }
 
 impl Camera {
-    const FORWARD: Vec3 = Vec3::new(0.0, 0.0, -1.0);
-    const BACKWARD: Vec3 = Vec3::new(0.0, 0.0, 1.0);
-    const LEFT: Vec3 = Vec3::new(-1.0, 0.0, 0.0);
-    const RIGHT: Vec3 = Vec3::new(1.0, 0.0, 0.0);
-
-    pub fn update(&mut self, input: &WinitInputHelper) {
-        if input.key_held(VirtualKeyCode::W) {
-            self.origin += Camera::FORWARD * self.speed;
+    /// A camera at the world origin looking down -z with a 60° field of view.
+    pub fn new(width: u32, height: u32) -> Camera {
+        Camera {
+            viewport_width: width as f32,
+            viewport_height: height as f32,
+            fov: Camera::compute_fov(60.0),
+            origin: Vec3::zero(),
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 0.1,
+            motion: Vec3::zero(),
         }
+    }
 
-        if input.key_held(VirtualKeyCode::S) {
-            self.origin += Camera::BACKWARD * self.speed;
+    /// Sets the vertical field of view in degrees.
+    pub fn with_fov(self, degrees: f32) -> Camera {
+        Camera {
+            fov: Camera::compute_fov(degrees),
+            ..self
         }
+    }
 
-        if input.key_held(VirtualKeyCode::A) {
-            self.origin += Camera::LEFT * self.speed;
-        }
+    pub fn with_origin(self, origin: Vec3) -> Camera {
+        Camera { origin, ..self }
+    }
 
-        if input.key_held(VirtualKeyCode::D) {
-            self.origin += Camera::RIGHT * self.speed;
+    /// Turns the camera `yaw` degrees to the right and `pitch` degrees up.
+    pub fn with_rotation(self, yaw: f32, pitch: f32) -> Camera {
+        Camera {
+            yaw: yaw.to_radians(),
+            pitch: pitch.to_radians(),
+            ..self
         }
     }
 
-    pub fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.viewport_width = size.width as f32;
-        self.viewport_height = size.height as f32;
+    pub fn origin_at(&self, time: f32) -> Vec3 {
+        self.origin + self.motion * time
+    }
+
+    /// Rotates a direction from camera space, where the camera looks down -z, into world space.
+    pub fn orientation(&self) -> Mat3 {
+        Mat3::from_rotation_y(-self.yaw) * Mat3::from_rotation_x(-self.pitch)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.viewport_width = width as f32;
+        self.viewport_height = height as f32;
     }
 
     pub fn aspect_ratio(&self) -> f32 {
@@ -1,7 +1,7 @@
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Sub};
 
-use image::{Pixel, Rgba};
-use serde::{Deserialize, Deserializer};
+use image::Rgba;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
@@ -23,19 +23,39 @@ impl Color {
         }
     }
 
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Self {
+            r: f(self.r),
+            g: f(self.g),
+            b: f(self.b),
+        }
+    }
+
+    /// Encodes the color as 8-bit sRGB with an opaque alpha channel.
     pub fn as_slice(&self) -> [u8; 4] {
-        let r = Self::gamma_encode(self.r);
-        let g = Self::gamma_encode(self.g);
-        let b = Self::gamma_encode(self.b);
+        let r = Self::quantize(Self::srgb_encode(self.r));
+        let g = Self::quantize(Self::srgb_encode(self.g));
+        let b = Self::quantize(Self::srgb_encode(self.b));
         [r, g, b, 255]
     }
 
+    /// Decodes a `0xRRGGBB` sRGB color into linear space.
     pub fn from_hex(hex: u32) -> Self {
-        let r = ((hex >> 16) & 0xFF) as f32 / 255.0;
-        let g = ((hex >> 8) & 0xFF) as f32 / 255.0;
-        let b = (hex & 0xFF) as f32 / 255.0;
+        let r = (hex >> 16) as u8;
+        let g = (hex >> 8) as u8;
+        let b = hex as u8;
 
-        Self { r, g, b }
+        Self::from_bytes([r, g, b], ColorSpace::Srgb)
+    }
+
+    pub fn from_bytes([r, g, b]: [u8; 3], space: ColorSpace) -> Self {
+        let decode = |value: u8| space.decode(value as f32 / 255.0);
+
+        Self {
+            r: decode(r),
+            g: decode(g),
+            b: decode(b),
+        }
     }
 
     pub fn lerp(a: Color, b: Color, t: f32) -> Color {
@@ -46,10 +66,45 @@ impl Color {
         }
     }
 
-    const GAMMA: f32 = 2.2;
+    /// The piecewise sRGB transfer function (IEC 61966-2-1).
+    pub fn srgb_encode(linear: f32) -> f32 {
+        if linear <= 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    pub fn srgb_decode(encoded: f32) -> f32 {
+        if encoded <= 0.04045 {
+            encoded / 12.92
+        } else {
+            ((encoded + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn quantize(value: f32) -> u8 {
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
+/// How stored color values relate to linear light.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    /// Display-referred values, as used by photos and color pickers.
+    #[default]
+    Srgb,
+    /// Values that are already linear, such as roughness or normal maps.
+    Linear,
+}
 
-    fn gamma_encode(linear: f32) -> u8 {
-        (linear.powf(1.0 / Self::GAMMA) * 255.0) as u8
+impl ColorSpace {
+    pub fn decode(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb => Color::srgb_decode(value),
+            ColorSpace::Linear => value,
+        }
     }
 }
 
@@ -66,24 +121,25 @@ impl Default for Color {
 impl From<Rgba<u8>> for Color {
     fn from(value: Rgba<u8>) -> Self {
         let Rgba([r, g, b, _]) = value;
+        Self::from_bytes([r, g, b], ColorSpace::Srgb)
+    }
+}
 
-        Self {
-            r: r as f32 / 255.0,
-            g: g as f32 / 255.0,
-            b: b as f32 / 255.0,
-        }
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl From<Color> for [f32; 3] {
+    fn from(value: Color) -> Self {
+        [value.r, value.g, value.b]
     }
 }
 
 impl From<[u8; 3]> for Color {
     fn from(value: [u8; 3]) -> Self {
-        let [r, g, b] = value;
-
-        Self {
-            r: r as f32 / 255.0,
-            g: g as f32 / 255.0,
-            b: b as f32 / 255.0,
-        }
+        Self::from_bytes(value, ColorSpace::Srgb)
     }
 }
 
@@ -97,6 +153,26 @@ impl<'de> Deserialize<'de> for Color {
         match format {
             ColorFormat::Hex(hex) => Ok(Color::from_hex(hex)),
             ColorFormat::Rgb(rgb) => Ok(Color::from(rgb)),
+            ColorFormat::Linear { linear } => Ok(Color::from(linear)),
+        }
+    }
+}
+
+/// Written as `[r, g, b]` sRGB bytes like most configs when they hold the color exactly, and
+/// as `{ linear: [r, g, b] }` otherwise, so that colors brighter than white or picked from a
+/// range keep their value.
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let [r, g, b, _] = self.as_slice();
+
+        if Color::from([r, g, b]) == *self {
+            ColorFormat::Rgb([r, g, b]).serialize(serializer)
+        } else {
+            let linear = (*self).into();
+            ColorFormat::Linear { linear }.serialize(serializer)
         }
     }
 }
@@ -113,6 +189,18 @@ impl Add<Color> for Color {
     }
 }
 
+impl Sub<Color> for Color {
+    type Output = Color;
+
+    fn sub(self, rhs: Color) -> Self::Output {
+        Self::Output {
+            r: self.r - rhs.r,
+            g: self.g - rhs.g,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
 impl Mul<Color> for Color {
     type Output = Color;
 
@@ -137,9 +225,75 @@ impl Mul<f32> for Color {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 enum ColorFormat {
     Hex(u32),
     Rgb([u8; 3]),
+    /// Linear channels, which may go past `1.0`.
+    Linear {
+        linear: [f32; 3],
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::{Color, ColorSpace};
+
+    #[test]
+    fn srgb_round_trips_every_byte() {
+        for byte in 0..=255u8 {
+            let color = Color::from_bytes([byte; 3], ColorSpace::Srgb);
+            assert_eq!(color.as_slice(), [byte, byte, byte, 255]);
+        }
+    }
+
+    #[test]
+    fn hex_is_decoded_from_srgb() {
+        let color = Color::from_hex(0xFF8000);
+        let [r, g, b] = color.into();
+
+        assert_eq!(r, 1.0);
+        assert!((g - 0.2158605).abs() < 1e-6);
+        assert_eq!(b, 0.0);
+    }
+
+    #[test]
+    fn linear_bytes_are_not_decoded() {
+        let color = Color::from_bytes([51, 102, 255], ColorSpace::Linear);
+        assert_eq!(color, Color::new(0.2, 0.4, 1.0));
+    }
+
+    #[test]
+    fn bytes_are_clamped_and_opaque() {
+        assert_eq!(Color::new(-1.0, 0.5, 4.0).as_slice(), [0, 188, 255, 255]);
+    }
+
+    #[test]
+    fn colors_are_saved_without_losing_precision() {
+        let yaml = |color: Color| serde_yaml::to_string(&color).unwrap();
+
+        assert_eq!(yaml(Color::from_hex(0xFF8000)), "- 255\n- 128\n- 0\n");
+        assert_eq!(
+            yaml(Color::new(2.0, 0.0, 0.5)),
+            "linear:\n- 2.0\n- 0.0\n- 0.5\n"
+        );
+
+        for color in [Color::from_hex(0x123456), Color::new(2.0, 0.3, 0.123456)] {
+            let saved: Color = serde_yaml::from_str(&yaml(color)).unwrap();
+            assert_eq!(saved, color);
+        }
+    }
+
+    #[test]
+    fn pixels_and_arrays_convert_both_ways() {
+        let pixel = Color::from(Rgba([255, 128, 0, 7]));
+        assert_eq!(pixel, Color::from([255, 128, 0]));
+        assert_eq!(pixel, Color::from_hex(0xFF8000));
+
+        let linear = [0.25, 0.5, 2.0];
+        assert_eq!(<[f32; 3]>::from(Color::from(linear)), linear);
+    }
 }
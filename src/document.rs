@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use crate::format::Format;
+
+/// A config file with its includes merged in, YAML merge keys applied and named materials
+/// substituted, ready to be deserialized.
+pub struct Document {
+    pub value: Value,
+    /// Every file pulled in through `include`, in the order they were read.
+    pub includes: Vec<PathBuf>,
+    locations: Locations,
+}
+
+/// Where a value was written, with 1-based line and column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+/// Locations keyed by paths such as `entity[2].material.albedo`.
+type Locations = HashMap<String, Location>;
+
+impl Document {
+    pub fn open(path: &Path, format: Format) -> anyhow::Result<Document> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Document::parse(&text, path, format)
+    }
+
+    /// Parses `text` as if it was read from `path`, which includes are relative to. Included
+    /// files are read in the format their extension names, or `format` if it names none.
+    pub fn parse(text: &str, path: &Path, format: Format) -> anyhow::Result<Document> {
+        let mut loader = Loader {
+            includes: Vec::new(),
+            stack: Vec::new(),
+        };
+
+        let (value, locations) = loader
+            .load(text, path, format)
+            .with_context(|| format!("in {}", path.display()))?;
+        Document::resolve(value, loader.includes, locations)
+    }
+
+    /// Finds where the value at `path` was written, or the closest parent that was.
+    pub fn locate(&self, path: &str) -> Option<&Location> {
+        let mut path = path;
+
+        loop {
+            if let Some(location) = self.locations.get(path) {
+                return Some(location);
+            }
+
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+
+    fn resolve(
+        mut value: Value,
+        includes: Vec<PathBuf>,
+        locations: Locations,
+    ) -> anyhow::Result<Document> {
+        if let Value::Mapping(document) = &mut value {
+            let materials = match document.remove("materials") {
+                Some(Value::Mapping(materials)) => materials,
+                Some(_) => bail!("`materials` must map names to materials"),
+                None => Mapping::new(),
+            };
+
+            if let Some(Value::Sequence(entities)) = document.get_mut("entity") {
+                for (index, entity) in entities.iter_mut().enumerate() {
+                    if let Some(material) = entity.get_mut("material") {
+                        resolve_material(material, &materials, &mut Vec::new())
+                            .with_context(|| format!("entity[{index}].material"))?;
+                    }
+                }
+            }
+
+            if let Some(Value::Sequence(generators)) = document.get_mut("generate") {
+                for (index, generator) in generators.iter_mut().enumerate() {
+                    if let Some(material) = generator
+                        .get_mut("entity")
+                        .and_then(|entity| entity.get_mut("material"))
+                    {
+                        resolve_material(material, &materials, &mut Vec::new())
+                            .with_context(|| format!("generate[{index}].entity.material"))?;
+                    }
+                }
+            }
+        }
+
+        Ok(Document {
+            value,
+            includes,
+            locations,
+        })
+    }
+}
+
+struct Loader {
+    includes: Vec<PathBuf>,
+    /// Files currently being loaded, to catch include cycles.
+    stack: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Include {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl Loader {
+    fn load(
+        &mut self,
+        text: &str,
+        path: &Path,
+        format: Format,
+    ) -> anyhow::Result<(Value, Locations)> {
+        let mut value = format.parse(text).context("parsing config file")?;
+        value.apply_merge().context("applying merge keys")?;
+
+        let mut locations = if format.has_locations() {
+            index(text, path)
+        } else {
+            Locations::new()
+        };
+
+        let include = match &mut value {
+            Value::Mapping(document) => document.remove("include"),
+            _ => None,
+        };
+
+        let paths = match include.map(serde_yaml::from_value).transpose() {
+            Ok(None) => return Ok((value, locations)),
+            Ok(Some(Include::One(path))) => vec![path],
+            Ok(Some(Include::Many(paths))) => paths,
+            Err(_) => bail!("`include` must be a path or a list of paths"),
+        };
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut merged = (Value::Mapping(Mapping::new()), Locations::new());
+
+        for path in paths {
+            let path = dir.join(path);
+            let key = canonical(&path);
+            ensure!(
+                !self.stack.contains(&key),
+                "{} includes itself",
+                path.display()
+            );
+
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("reading {}", path.display()))?;
+
+            self.includes.push(path.clone());
+            self.stack.push(key);
+            let format = Format::from_path(&path).unwrap_or(format);
+            let included = self
+                .load(&text, &path, format)
+                .with_context(|| format!("in {}", path.display()))?;
+            self.stack.pop();
+
+            merge(&mut merged, included);
+        }
+
+        locations.retain(|path, _| path != "include" && !path.starts_with("include["));
+        merge(&mut merged, (value, locations));
+        Ok(merged)
+    }
+}
+
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Merges a document into the ones included before it: lists such as `entity` are
+/// concatenated, and everything else is overlaid.
+fn merge((base, locations): &mut (Value, Locations), (document, mut moved): (Value, Locations)) {
+    let (Value::Mapping(base), Value::Mapping(document)) = (&mut *base, &document) else {
+        *base = document;
+        *locations = moved;
+        return;
+    };
+
+    for (key, value) in document.clone() {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Sequence(list)), Value::Sequence(items)) => {
+                if let Some(key) = key.as_str() {
+                    moved = moved
+                        .into_iter()
+                        .map(|(path, location)| (shift(&path, key, list.len()), location))
+                        .collect();
+                }
+
+                list.extend(items);
+            }
+            (Some(existing), value) => overlay(existing, value),
+            (None, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+
+    locations.extend(moved);
+}
+
+/// Renumbers `path` if it points into the list `key`, whose items now start at `offset`.
+fn shift(path: &str, key: &str, offset: usize) -> String {
+    let item = path
+        .strip_prefix(key)
+        .and_then(|rest| rest.strip_prefix('['))
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(index, rest)| Some((index.parse::<usize>().ok()?, rest)));
+
+    match item {
+        Some((index, rest)) => format!("{key}[{}]{rest}", index + offset),
+        None => path.to_string(),
+    }
+}
+
+/// Replaces `base` with `value`, except that the fields of nested mappings are replaced one
+/// at a time.
+fn overlay(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(existing) => overlay(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// Replaces a material name with its definition from the `materials` library. A mapping with
+/// a `base` name starts from that definition and overrides the fields it sets.
+fn resolve_material(
+    material: &mut Value,
+    library: &Mapping,
+    seen: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let (name, overrides) = match material {
+        Value::String(name) => (name.clone(), None),
+        Value::Mapping(fields) => match fields.remove("base") {
+            Some(Value::String(name)) => (name, Some(std::mem::take(fields))),
+            Some(_) => bail!("`base` must be the name of a material"),
+            None => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+
+    ensure!(
+        !seen.contains(&name),
+        "material `{name}` is based on itself"
+    );
+
+    let mut resolved = library
+        .get(name.as_str())
+        .with_context(|| format!("there is no material named `{name}`"))?
+        .clone();
+
+    seen.push(name);
+    resolve_material(&mut resolved, library, seen)?;
+
+    if let Some(overrides) = overrides {
+        overlay(&mut resolved, Value::Mapping(overrides));
+    }
+
+    *material = resolved;
+    Ok(())
+}
+
+/// Records where every value in `text` starts. Values that are only reachable through an alias
+/// or a merge key are not recorded and fall back to a parent in `Document::locate`.
+fn index(text: &str, file: &Path) -> Locations {
+    let mut indexer = Indexer {
+        file,
+        stack: Vec::new(),
+        locations: Locations::new(),
+    };
+
+    // Syntax errors are already reported by the real parser.
+    let _ = Parser::new_from_str(text).load(&mut indexer, false);
+    indexer.locations
+}
+
+struct Indexer<'a> {
+    file: &'a Path,
+    stack: Vec<Node>,
+    locations: Locations,
+}
+
+enum Node {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+impl Indexer<'_> {
+    /// Returns the path of a value starting at `mark`, recording it for sequence items. Mapping
+    /// values are recorded at their key instead, and block mappings only report where they
+    /// start once their first key has been scanned, so they are recorded at that key.
+    fn enter(&mut self, mark: Marker, mapping: bool) -> String {
+        match self.stack.last_mut() {
+            None => String::new(),
+            Some(Node::Mapping { path, key }) => join(path, &key.take().unwrap_or_default()),
+            Some(Node::Sequence { path, index }) => {
+                let path = format!("{path}[{index}]");
+                *index += 1;
+                if !mapping {
+                    self.record(path.clone(), mark);
+                }
+                path
+            }
+        }
+    }
+
+    fn record(&mut self, path: String, mark: Marker) {
+        let location = Location {
+            file: self.file.to_path_buf(),
+            line: mark.line(),
+            column: mark.col() + 1,
+        };
+
+        self.locations.insert(path, location);
+    }
+}
+
+impl MarkedEventReceiver for Indexer<'_> {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                if let Some(Node::Mapping {
+                    path,
+                    key: key @ None,
+                }) = self.stack.last_mut()
+                {
+                    let parent = path.clone();
+                    let path = join(path, &value);
+                    *key = Some(value);
+
+                    if !self.locations.contains_key(&parent) {
+                        self.record(parent, mark);
+                    }
+                    self.record(path, mark);
+                } else {
+                    self.enter(mark, false);
+                }
+            }
+            Event::Alias(_) => {
+                self.enter(mark, false);
+            }
+            Event::MappingStart(..) => {
+                let path = self.enter(mark, true);
+                self.stack.push(Node::Mapping { path, key: None });
+            }
+            Event::SequenceStart(..) => {
+                let path = self.enter(mark, false);
+                self.stack.push(Node::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_yaml::Value;
+
+    use super::{Document, Format, Location};
+
+    fn parse(text: &str) -> Value {
+        Document::parse(text, Path::new("test.yaml"), Format::Yaml)
+            .unwrap()
+            .value
+    }
+
+    #[test]
+    fn named_materials_with_overrides() {
+        let value = parse(
+            "
+            materials:
+              steel:
+                albedo: 0.5
+                surface: { reflectivity: 0.8, roughness: 0.3 }
+              polished:
+                base: steel
+                surface: { roughness: 0.0 }
+            entity:
+              - material: steel
+              - material: { base: polished, albedo: 0.2 }
+            ",
+        );
+
+        assert!(value.get("materials").is_none());
+
+        let first = &value["entity"][0]["material"];
+        assert_eq!(first["albedo"], 0.5);
+
+        let second = &value["entity"][1]["material"];
+        assert_eq!(second["albedo"], 0.2);
+        assert_eq!(second["surface"]["reflectivity"], 0.8);
+        assert_eq!(second["surface"]["roughness"], 0.0);
+    }
+
+    #[test]
+    fn unknown_and_cyclic_materials_are_errors() {
+        let unknown = "entity:\n  - material: missing";
+        assert!(Document::parse(unknown, Path::new("test.yaml"), Format::Yaml).is_err());
+
+        let cyclic = "
+            materials:
+              a: { base: b }
+              b: { base: a }
+            entity:
+              - material: a
+            ";
+        assert!(Document::parse(cyclic, Path::new("test.yaml"), Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn merge_keys_override_anchored_fields() {
+        let value = parse(
+            "
+            shared: &shared
+              albedo: 0.5
+              color: 0xFF0000
+            entity:
+              - material:
+                  <<: *shared
+                  albedo: 0.9
+            ",
+        );
+
+        let material = &value["entity"][0]["material"];
+        assert_eq!(material["albedo"], 0.9);
+        assert_eq!(material["color"], 0xFF0000);
+    }
+
+    #[test]
+    fn locations_follow_paths() {
+        let document = Document::parse(
+            "camera:\n  width: 400\nentity:\n  - type: sphere\n    material:\n      albedo: 2.0\n  - { type: plane }\n",
+            Path::new("test.yaml"),
+            Format::Yaml,
+        )
+        .unwrap();
+
+        let at = |path| {
+            let Location { line, column, .. } = document.locate(path).unwrap();
+            (*line, *column)
+        };
+
+        assert_eq!(at("camera.width"), (2, 3));
+        assert_eq!(at("entity[0]"), (4, 5));
+        assert_eq!(at("entity[0].material.albedo"), (6, 7));
+        assert_eq!(at("entity[1].type"), (7, 7));
+        // Paths that were never written fall back to their nearest written parent.
+        assert_eq!(at("entity[1].material.color"), at("entity[1]"));
+    }
+
+    #[test]
+    fn includes_are_relative_to_the_including_file() {
+        let dir = std::env::temp_dir().join(format!("include-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+
+        std::fs::write(
+            dir.join("lib/materials.yaml"),
+            "include: lights.yaml\nmaterials:\n  red: { color: 0xFF0000 }\nreflection_depth: 2",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lib/lights.yaml"),
+            "light:\n  - { type: point, intensity: 1 }",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("scene.yaml"),
+            "include: [lib/materials.yaml]\nreflection_depth: 4\nlight:\n  - { type: directional }\nentity:\n  - material: red",
+        )
+        .unwrap();
+
+        let document = Document::open(&dir.join("scene.yaml"), Format::Yaml).unwrap();
+        let value = &document.value;
+
+        assert_eq!(document.includes.len(), 2);
+        assert_eq!(value["reflection_depth"], 4);
+        assert_eq!(value["light"][0]["type"], "point");
+        assert_eq!(value["light"][1]["type"], "directional");
+        assert_eq!(value["entity"][0]["material"]["color"], 0xFF0000);
+
+        let light = document.locate("light[1].type").unwrap();
+        assert_eq!(light.file, dir.join("scene.yaml"));
+        assert_eq!((light.line, light.column), (4, 7));
+
+        let color = document.locate("entity[0].material.color").unwrap();
+        assert_eq!(color.file, dir.join("scene.yaml"));
+        assert_eq!(color.line, 6);
+
+        std::fs::write(dir.join("lib/lights.yaml"), "include: ../scene.yaml").unwrap();
+        assert!(Document::open(&dir.join("scene.yaml"), Format::Yaml).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::aov::AovSample;
+use crate::color::Color;
+
+/// Linear, unclamped radiance for every pixel of the frame, optionally along with the
+/// AOV samples of the camera rays. Pixels hold the running average of every sample
+/// accumulated since the last reset.
+#[derive(Debug, Clone, Default)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    aovs: Option<Vec<AovSample>>,
+    samples: u32,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::default(); (width * height) as usize],
+            aovs: None,
+            samples: 0,
+        }
+    }
+
+    pub fn with_aovs(width: u32, height: u32) -> Framebuffer {
+        let mut buffer = Framebuffer::new(width, height);
+        buffer.aovs = Some(vec![AovSample::default(); buffer.pixels.len()]);
+        buffer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn aovs(&self) -> Option<&[AovSample]> {
+        self.aovs.as_deref()
+    }
+
+    /// Number of samples per pixel accumulated so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Blends a new sample into the running average of pixel `index`, along with its AOVs.
+    pub fn accumulate(&mut self, index: usize, color: Color, aov: AovSample) {
+        let weight = 1.0 / (self.samples + 1) as f32;
+        self.pixels[index] = Color::lerp(self.pixels[index], color, weight);
+
+        if let Some(aovs) = &mut self.aovs {
+            aovs[index] = aovs[index].blend(aov, weight);
+        }
+    }
+
+    /// Marks the end of a pass over every pixel.
+    pub fn finish_sample(&mut self) {
+        self.samples += 1;
+    }
+
+    /// Starts accumulating from scratch.
+    pub fn reset(&mut self) {
+        self.samples = 0;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self = if self.aovs.is_some() {
+            Framebuffer::with_aovs(width, height)
+        } else {
+            Framebuffer::new(width, height)
+        };
+    }
+
+    /// Nearest-neighbour enlargement to `width` by `height`.
+    pub fn upscale(&self, width: u32, height: u32) -> Framebuffer {
+        let mut output = Framebuffer::new(width, height);
+
+        for (index, pixel) in output.pixels.iter_mut().enumerate() {
+            let x = index as u32 % width * self.width / width;
+            let y = index as u32 / width * self.height / height;
+            *pixel = self.pixels[(y * self.width + x) as usize];
+        }
+
+        output
+    }
+
+    /// Tone maps and encodes every pixel into an RGBA8 frame.
+    pub fn write_rgba(&self, tone_mapping: &ToneMapping, frame: &mut [u8]) {
+        for (pixel, color) in frame.chunks_exact_mut(4).zip(&self.pixels) {
+            pixel.copy_from_slice(&tone_mapping.apply(*color).as_slice());
+        }
+    }
+
+    pub fn to_image(&self, tone_mapping: &ToneMapping) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        self.write_rgba(tone_mapping, &mut image);
+        image
+    }
+}
+
+/// How linear radiance is squeezed into the displayable range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    /// Clips everything above the white point.
+    #[default]
+    Clamp,
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// Approximation of Blender's AgX view transform.
+    Agx,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ToneMapping {
+    #[serde(default)]
+    pub operator: Operator,
+    /// Exposure adjustment in stops.
+    #[serde(default)]
+    pub exposure: f32,
+    /// Linear value that maps to display white. Defaults to the operator's own range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub white_point: Option<f32>,
+}
+
+impl ToneMapping {
+    pub fn apply(&self, color: Color) -> Color {
+        let exposed = color * self.exposure.exp2();
+        let mapped = self.curve(exposed);
+
+        let mapped = match self.white_point {
+            Some(white) => {
+                let [reference, _, _] = self.curve(Color::new(white, white, white)).into();
+                mapped * (1.0 / reference)
+            }
+            None => mapped,
+        };
+
+        mapped.clamp()
+    }
+
+    fn curve(&self, color: Color) -> Color {
+        match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard => color.map(|x| x / (1.0 + x)),
+            Operator::Aces => color.map(|x| {
+                let x = x.max(0.0);
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            Operator::Agx => agx(color),
+        }
+    }
+}
+
+fn agx(color: Color) -> Color {
+    const INSET: [[f32; 3]; 3] = [
+        [0.84247906, 0.0784336, 0.07922375],
+        [0.04232824, 0.87846864, 0.07916613],
+        [0.04237565, 0.0784336, 0.879143],
+    ];
+    const OUTSET: [[f32; 3]; 3] = [
+        [1.196879, -0.09802088, -0.09902974],
+        [-0.05289685, 1.1519031, -0.09896118],
+        [-0.05297164, -0.09804345, 1.1510737],
+    ];
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+
+    let encoded = multiply(INSET, color.into()).map(|x| {
+        let log = x.max(1e-10).log2().clamp(MIN_EV, MAX_EV);
+        contrast((log - MIN_EV) / (MAX_EV - MIN_EV))
+    });
+
+    let display = multiply(OUTSET, encoded);
+    Color::from(display).map(|x| x.max(0.0).powf(2.2))
+}
+
+/// Polynomial fit of AgX's default contrast curve.
+fn contrast(x: f32) -> f32 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+}
+
+fn multiply(matrix: [[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row.iter().zip(vector).map(|(a, b)| a * b).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aov::AovSample;
+    use crate::color::Color;
+
+    use super::{Framebuffer, Operator, ToneMapping};
+
+    #[test]
+    fn accumulation_averages_samples_until_reset() {
+        let mut buffer = Framebuffer::new(1, 1);
+
+        for value in [1.0, 2.0, 6.0] {
+            buffer.accumulate(0, Color::new(value, value, value), AovSample::default());
+            buffer.finish_sample();
+        }
+
+        assert_eq!(buffer.samples(), 3);
+        assert_eq!(buffer.pixels()[0], Color::new(3.0, 3.0, 3.0));
+
+        buffer.reset();
+        buffer.accumulate(0, Color::new(0.5, 0.5, 0.5), AovSample::default());
+        assert_eq!(buffer.pixels()[0], Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn aovs_are_averaged_like_the_beauty_pass() {
+        let mut buffer = Framebuffer::with_aovs(1, 1);
+        let gray = |value: f32| Color::new(value, value, value);
+        let sample = |beauty: f32, direct: f32, depth: f32| AovSample {
+            depth,
+            entity_id: depth,
+            direct: gray(direct),
+            indirect: gray(beauty - direct),
+            ..AovSample::default()
+        };
+
+        for (beauty, direct, depth) in [(1.0, 1.0, 2.0), (4.0, 0.0, 4.0), (4.0, 2.0, 6.0)] {
+            buffer.accumulate(0, gray(beauty), sample(beauty, direct, depth));
+            buffer.finish_sample();
+        }
+
+        let aov = buffer.aovs().unwrap()[0];
+        assert_eq!(buffer.pixels()[0], gray(3.0));
+        assert_eq!(aov.direct, gray(1.0));
+        assert_eq!(aov.direct + aov.indirect, buffer.pixels()[0]);
+        assert_eq!(aov.depth, 4.0);
+        assert_eq!(aov.entity_id, 2.0);
+
+        buffer.accumulate(0, gray(1.0), sample(1.0, 1.0, f32::INFINITY));
+        assert_eq!(buffer.aovs().unwrap()[0].depth, f32::INFINITY);
+    }
+
+    fn tone_mapping(operator: Operator) -> ToneMapping {
+        ToneMapping {
+            operator,
+            ..ToneMapping::default()
+        }
+    }
+
+    #[test]
+    fn operators_keep_black_and_compress_highlights() {
+        for operator in [
+            Operator::Clamp,
+            Operator::Reinhard,
+            Operator::Aces,
+            Operator::Agx,
+        ] {
+            let tone_mapping = tone_mapping(operator);
+            let [black, _, _] = tone_mapping.apply(Color::default()).into();
+            let [bright, _, _] = tone_mapping.apply(Color::new(100.0, 100.0, 100.0)).into();
+
+            assert!(black < 0.01, "{operator:?} lifts black to {black}");
+            assert!(
+                bright > 0.9 && bright <= 1.0,
+                "{operator:?} maps 100 to {bright}"
+            );
+        }
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let tone_mapping = ToneMapping {
+            exposure: 1.0,
+            ..ToneMapping::default()
+        };
+
+        assert_eq!(
+            tone_mapping.apply(Color::new(0.25, 0.25, 0.25)),
+            Color::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn white_point_maps_to_display_white() {
+        let tone_mapping = ToneMapping {
+            operator: Operator::Reinhard,
+            white_point: Some(4.0),
+            ..ToneMapping::default()
+        };
+
+        let [white, _, _] = tone_mapping.apply(Color::new(4.0, 4.0, 4.0)).into();
+        assert!((white - 1.0).abs() < 1e-5);
+    }
+}
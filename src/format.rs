@@ -0,0 +1,191 @@
+//! File formats a config can be written in. Every format is read into the same YAML value,
+//! so includes, named materials, validation and defaults work the same in all of them.
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde_yaml::Value;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+    /// Rusty Object Notation. Enum values such as `type` are written as strings.
+    Ron,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Yaml, Format::Json, Format::Toml, Format::Ron];
+
+    /// Picks the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "ron" => Some(Format::Ron),
+            _ => None,
+        }
+    }
+
+    /// The usual extension of files in this format, which is also its name on the command
+    /// line.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Yaml => "yaml",
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Ron => "ron",
+        }
+    }
+
+    /// Whether the YAML parser can find where values were written in a file of this format,
+    /// which holds for JSON as it is a subset of YAML.
+    pub fn has_locations(self) -> bool {
+        matches!(self, Format::Yaml | Format::Json)
+    }
+
+    pub fn parse(self, text: &str) -> anyhow::Result<Value> {
+        Ok(match self {
+            Format::Yaml => serde_yaml::from_str(text)?,
+            Format::Json => serde_json::from_str(text)?,
+            Format::Toml => toml::from_str(text)?,
+            Format::Ron => ron::from_str(text)?,
+        })
+    }
+
+    pub fn write(self, value: &impl Serialize) -> anyhow::Result<String> {
+        // Going through a YAML value first writes every format from the same tree, which
+        // keeps enums as the plain strings all of them read back.
+        let value = serde_yaml::to_value(value)?;
+
+        Ok(match self {
+            Format::Yaml => serde_yaml::to_string(&value)?,
+            Format::Json => serde_json::to_string_pretty(&value)?,
+            Format::Toml => toml::to_string_pretty(&value)?,
+            Format::Ron => ron::ser::to_string_pretty(&value, Default::default())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::document::Document;
+    use crate::registry::Registry;
+    use crate::serialize::WorldConfig;
+
+    use super::Format;
+
+    fn load(text: &str, format: Format) -> serde_yaml::Value {
+        let path = Path::new("test").with_extension(format.extension());
+        let document = Document::parse(text, &path, format).unwrap();
+        let config: WorldConfig = serde_yaml::from_value(document.value).unwrap();
+        serde_yaml::to_value(config).unwrap()
+    }
+
+    #[test]
+    fn formats_are_picked_by_extension() {
+        assert_eq!(
+            Format::from_path(Path::new("a/scene.yml")),
+            Some(Format::Yaml)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("scene.JSON")),
+            Some(Format::Json)
+        );
+        assert_eq!(Format::from_path(Path::new("scene.ron")), Some(Format::Ron));
+        assert_eq!(Format::from_path(Path::new("scene.txt")), None);
+    }
+
+    #[test]
+    fn hand_written_configs_load_the_same() {
+        let yaml = "
+camera: { width: 40, height: 30 }
+light:
+  - { type: point, position: [0, -5, 0], color: 0xFFFFFF, intensity: 100 }
+entity:
+  - type: sphere
+    position: [0, 0, -4]
+    radius: 1
+    material: { color: [255, 128, 0], albedo: 0.8, surface: { reflectivity: 0.4 } }
+tone_mapping: { operator: aces }
+";
+        let json = r#"{
+  "camera": { "width": 40, "height": 30 },
+  "light": [{ "type": "point", "position": [0, -5, 0], "color": 16777215, "intensity": 100 }],
+  "entity": [{
+    "type": "sphere", "position": [0, 0, -4], "radius": 1,
+    "material": { "color": [255, 128, 0], "albedo": 0.8, "surface": { "reflectivity": 0.4 } }
+  }],
+  "tone_mapping": { "operator": "aces" }
+}"#;
+        let toml = r#"
+camera = { width = 40, height = 30 }
+tone_mapping = { operator = "aces" }
+
+[[light]]
+type = "point"
+position = [0, -5, 0]
+color = 0xFFFFFF
+intensity = 100
+
+[[entity]]
+type = "sphere"
+position = [0, 0, -4]
+radius = 1
+material = { color = [255, 128, 0], albedo = 0.8, surface = { reflectivity = 0.4 } }
+"#;
+        let ron = r#"(
+    camera: (width: 40, height: 30),
+    light: [(type: "point", position: [0, -5, 0], color: 0xFFFFFF, intensity: 100)],
+    entity: [(
+        type: "sphere",
+        position: [0, 0, -4],
+        radius: 1,
+        material: (color: [255, 128, 0], albedo: 0.8, surface: (reflectivity: 0.4)),
+    )],
+    tone_mapping: (operator: "aces"),
+)"#;
+
+        let expected = load(yaml, Format::Yaml);
+        assert_eq!(expected["reflection_depth"], 5);
+
+        for (text, format) in [
+            (json, Format::Json),
+            (toml, Format::Toml),
+            (ron, Format::Ron),
+        ] {
+            assert_eq!(load(text, format), expected, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn configs_round_trip_through_every_format() {
+        let dir = std::env::temp_dir().join(format!("format-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["config", "fog", "library", "smoke", "spheres", "turntable"] {
+            let path = Path::new("assets").join(name).with_extension("yaml");
+            let (config, _) = WorldConfig::open(&path, Format::Yaml, &Registry::default()).unwrap();
+            let expected = serde_yaml::to_value(&config).unwrap();
+
+            for format in Format::ALL {
+                let saved = dir.join(name).with_extension(format.extension());
+                config.save(&saved, format).unwrap();
+
+                let (reloaded, _) =
+                    WorldConfig::open(&saved, format, &Registry::default()).unwrap();
+                let value = serde_yaml::to_value(&reloaded).unwrap();
+                assert_eq!(value, expected, "{name} as {format:?}");
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
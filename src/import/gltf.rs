@@ -0,0 +1,508 @@
+//! Importer for [glTF 2.0](https://www.khronos.org/gltf/) scenes and models, in `.gltf` or
+//! `.glb` files.
+//!
+//! Triangle meshes are placed by their node transforms, metallic-roughness materials keep
+//! their base color or base color texture, metallic and roughness, and `KHR_lights_punctual`
+//! lights become point, directional and spot lights. The first perspective camera found in the
+//! scene views it. Everything else, such as normal maps, emission and transparency, is skipped
+//! with a warning.
+//!
+//! glTF is y-up and right-handed, while our y points down, so the scene is moved into the
+//! space of its camera and turned upside down. Models loaded as a single entity are only
+//! turned upside down.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+
+use ::gltf::image::Format;
+use ::gltf::khr_lights_punctual::{Kind, Light as GltfLight};
+use ::gltf::mesh::Mode;
+use ::gltf::{buffer, camera::Projection, Document, Node};
+use anyhow::{bail, Context};
+use image::{DynamicImage, ImageBuffer};
+use ultraviolet::{Mat4, Vec2, Vec3};
+
+use crate::camera::Camera;
+use crate::color::{Color, ColorSpace};
+use crate::import::Import;
+use crate::light::directional::DirectionalLight;
+use crate::light::point::PointLight;
+use crate::light::spot::SpotLight;
+use crate::light::Light;
+use crate::model::group::Group;
+use crate::model::mesh::{Mesh, MeshData};
+use crate::model::Material;
+use crate::scene::Scene;
+use crate::texture::{Coloration, ImageTexture};
+use crate::validate::Diagnostic;
+use crate::world::World;
+
+pub fn open(path: &Path) -> anyhow::Result<Import> {
+    let (document, buffers, images) = read(path)?;
+    let mut importer = Importer::new(&document, &buffers, images);
+    let nodes = nodes(&document)?;
+
+    let camera = nodes
+        .iter()
+        .find_map(|(node, matrix)| Some((node.camera()?, *matrix)));
+    let (camera, to_ours) = match camera {
+        Some((camera, matrix)) => (importer.camera(&camera), flip() * matrix.inversed()),
+        None => (Camera::new(1280, 720), flip()),
+    };
+
+    let mut world = World::new();
+
+    for (node, matrix) in &nodes {
+        let matrix = to_ours * *matrix;
+
+        if let Some(mesh) = node.mesh() {
+            for mesh in importer.meshes(&mesh, matrix) {
+                world.entities.push(Box::new(mesh));
+            }
+        }
+
+        if let Some(light) = node.light() {
+            world.lights.push(light_at(&light, matrix));
+        }
+    }
+
+    Ok(Import {
+        scene: Scene::new(camera, world),
+        files: files(&document, path),
+        warnings: importer.warnings,
+    })
+}
+
+/// Loads every mesh of the scene in `path` as one entity, moved by `transform` after turning
+/// it y-down. Cameras and lights are left out.
+pub fn model(path: &Path, transform: Mat4) -> anyhow::Result<(Group, Vec<Diagnostic>)> {
+    let (document, buffers, images) = read(path)?;
+    let mut importer = Importer::new(&document, &buffers, images);
+    let mut meshes = Vec::new();
+
+    for (node, matrix) in nodes(&document)? {
+        if let Some(mesh) = node.mesh() {
+            meshes.extend(importer.meshes(&mesh, transform * flip() * matrix));
+        }
+    }
+
+    if meshes.is_empty() {
+        bail!("{} has no triangle meshes", path.display());
+    }
+
+    Ok((Group::new(meshes), importer.warnings))
+}
+
+/// Turns glTF's y-up space into our y-down one.
+fn flip() -> Mat4 {
+    Mat4::from_nonuniform_scale(Vec3::new(1.0, -1.0, 1.0))
+}
+
+fn read(path: &Path) -> anyhow::Result<(Document, Vec<buffer::Data>, Vec<::gltf::image::Data>)> {
+    ::gltf::import(path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Every node of the default scene, or of the first one if none is the default, along with
+/// its transform into the scene's space. Parents come before their children.
+fn nodes(document: &Document) -> anyhow::Result<Vec<(Node<'_>, Mat4)>> {
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .context("the file has no scenes")?;
+
+    let mut nodes = Vec::new();
+    for node in scene.nodes() {
+        visit(node, Mat4::identity(), &mut nodes);
+    }
+
+    Ok(nodes)
+}
+
+fn visit<'a>(node: Node<'a>, parent: Mat4, nodes: &mut Vec<(Node<'a>, Mat4)>) {
+    let matrix = parent * Mat4::from(node.transform().matrix());
+    nodes.push((node.clone(), matrix));
+
+    for child in node.children() {
+        visit(child, matrix, nodes);
+    }
+}
+
+/// The file the scene was opened from, followed by the buffers and images it refers to.
+fn files(document: &Document, path: &Path) -> Vec<PathBuf> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let buffers = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            buffer::Source::Uri(uri) => Some(uri),
+            buffer::Source::Bin => None,
+        });
+    let images = document.images().filter_map(|image| match image.source() {
+        ::gltf::image::Source::Uri { uri, .. } => Some(uri),
+        ::gltf::image::Source::View { .. } => None,
+    });
+
+    let files = buffers
+        .chain(images)
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| directory.join(uri));
+    std::iter::once(path.to_path_buf()).chain(files).collect()
+}
+
+struct Importer<'a> {
+    buffers: &'a [buffer::Data],
+    /// Decoded images, taken when a texture first uses them.
+    images: Vec<Option<::gltf::image::Data>>,
+    textures: HashMap<usize, Option<ImageTexture>>,
+    materials: HashMap<Option<usize>, Material>,
+    /// Material id of meshes without a material, following the ids of the file's materials.
+    default_material: usize,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(
+        document: &Document,
+        buffers: &'a [buffer::Data],
+        images: Vec<::gltf::image::Data>,
+    ) -> Importer<'a> {
+        Importer {
+            buffers,
+            images: images.into_iter().map(Some).collect(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            default_material: document.materials().len(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn warn(&mut self, path: String, message: impl Into<String>) {
+        self.warnings.push(Diagnostic::warning(path, message));
+    }
+
+    fn camera(&mut self, camera: &::gltf::Camera) -> Camera {
+        match camera.projection() {
+            Projection::Perspective(perspective) => {
+                let height = 720;
+                let width = perspective
+                    .aspect_ratio()
+                    .map_or(1280, |aspect| (height as f32 * aspect).round() as u32);
+
+                Camera::new(width, height).with_fov(perspective.yfov().to_degrees())
+            }
+            Projection::Orthographic(_) => {
+                let path = format!("cameras[{}]", camera.index());
+                self.warn(
+                    path,
+                    "orthographic cameras are imported as perspective ones",
+                );
+                Camera::new(1280, 720)
+            }
+        }
+    }
+
+    /// The triangle primitives of `mesh`, moved by `matrix`.
+    fn meshes(&mut self, mesh: &::gltf::Mesh, matrix: Mat4) -> Vec<Mesh> {
+        let mut meshes = Vec::new();
+
+        for primitive in mesh.primitives() {
+            let path = format!("meshes[{}].primitives[{}]", mesh.index(), primitive.index());
+            if primitive.mode() != Mode::Triangles {
+                self.warn(
+                    path,
+                    format!("{:?} primitives are skipped", primitive.mode()),
+                );
+                continue;
+            }
+
+            let buffers = self.buffers;
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let Some(positions) = reader.read_positions() else {
+                self.warn(path, "primitives without positions are skipped");
+                continue;
+            };
+
+            let positions: Vec<_> = positions.map(Vec3::from).collect();
+            let indices: Vec<_> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+
+            let mut data = MeshData {
+                normals: reader
+                    .read_normals()
+                    .map_or(Vec::new(), |normals| normals.map(Vec3::from).collect()),
+                uvs: reader
+                    .read_tex_coords(0)
+                    .map_or(Vec::new(), |uvs| uvs.into_f32().map(Vec2::from).collect()),
+                triangles: indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                    .collect(),
+                positions,
+                ..MeshData::default()
+            };
+
+            data.transform(matrix);
+            let material = self.material(&primitive.material());
+            match Mesh::new(data, material) {
+                Ok(mesh) => meshes.push(mesh),
+                Err(e) => self.warn(path, format!("{e}, so the primitive is skipped")),
+            }
+        }
+
+        meshes
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> Material {
+        if let Some(material) = self.materials.get(&material.index()) {
+            return material.clone();
+        }
+
+        let path = material
+            .index()
+            .map_or("the default material".into(), |i| format!("materials[{i}]"));
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let factor = Color::new(r, g, b);
+
+        let texture = pbr.base_color_texture().and_then(|info| {
+            if info.tex_coord() != 0 {
+                self.warn(path.clone(), "only the first texture coordinates are used");
+            }
+            self.texture(&info.texture())
+        });
+        let color = match texture {
+            Some(texture) if factor != Color::new(1.0, 1.0, 1.0) => {
+                self.warn(path.clone(), "the base color factor of textures is ignored");
+                Coloration::Texture(texture)
+            }
+            Some(texture) => Coloration::Texture(texture),
+            None => Coloration::Color(factor),
+        };
+
+        let unsupported = [
+            (
+                pbr.metallic_roughness_texture().is_some(),
+                "metallic-roughness",
+            ),
+            (material.normal_texture().is_some(), "normal"),
+            (material.occlusion_texture().is_some(), "occlusion"),
+            (material.emissive_texture().is_some(), "emissive"),
+        ];
+        for (_, name) in unsupported.iter().filter(|(used, _)| *used) {
+            self.warn(path.clone(), format!("{name} textures are skipped"));
+        }
+        if material.emissive_factor() != [0.0; 3] {
+            self.warn(path.clone(), "emission is skipped");
+        }
+        if material.alpha_mode() != ::gltf::material::AlphaMode::Opaque {
+            self.warn(path.clone(), "transparency is skipped");
+        }
+
+        let mut result = Material::new(color, 1.0);
+        if pbr.metallic_factor() > 0.0 {
+            result = result.reflective(pbr.metallic_factor(), pbr.roughness_factor());
+        }
+        result.id = material.index().unwrap_or(self.default_material);
+
+        self.materials.insert(material.index(), result.clone());
+        result
+    }
+
+    fn texture(&mut self, texture: &::gltf::Texture) -> Option<ImageTexture> {
+        let index = texture.source().index();
+        if let Some(texture) = self.textures.get(&index) {
+            return texture.clone();
+        }
+
+        let image = self.images[index].take().and_then(decode);
+        if image.is_none() {
+            let path = format!("images[{index}]");
+            self.warn(
+                path,
+                "the pixel format is not supported, so the image is skipped",
+            );
+        }
+
+        let texture = image.map(|image| ImageTexture::from_image(1.0, image, ColorSpace::Srgb));
+        self.textures.insert(index, texture.clone());
+        texture
+    }
+}
+
+/// glTF lights shine down their node's -z axis. Point and spot intensities are in candela,
+/// which our lights spread over the whole sphere.
+fn light_at(light: &GltfLight, matrix: Mat4) -> Box<dyn Light> {
+    let color = Color::from(light.color());
+    let position = matrix.transform_point3(Vec3::zero());
+    let direction = matrix.transform_vec3(-Vec3::unit_z());
+
+    match light.kind() {
+        Kind::Directional => Box::new(DirectionalLight::new(direction, color, light.intensity())),
+        Kind::Point => Box::new(PointLight::new(
+            position,
+            color,
+            4.0 * PI * light.intensity(),
+        )),
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => Box::new(
+            SpotLight::new(position, direction, color, 4.0 * PI * light.intensity())
+                .with_cone(inner_cone_angle.to_degrees(), outer_cone_angle.to_degrees()),
+        ),
+    }
+}
+
+/// Wraps decoded pixels in the image type that matches their format.
+fn decode(data: ::gltf::image::Data) -> Option<DynamicImage> {
+    let ::gltf::image::Data {
+        pixels,
+        format,
+        width,
+        height,
+    } = data;
+
+    let words = || -> Vec<u16> {
+        pixels
+            .chunks_exact(2)
+            .map(|word| u16::from_le_bytes([word[0], word[1]]))
+            .collect()
+    };
+    let floats = || -> Vec<f32> {
+        pixels
+            .chunks_exact(4)
+            .map(|float| f32::from_le_bytes([float[0], float[1], float[2], float[3]]))
+            .collect()
+    };
+
+    Some(match format {
+        Format::R8 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8A8 => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R16 => DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, words())?),
+        Format::R16G16 => {
+            DynamicImage::ImageLumaA16(ImageBuffer::from_raw(width, height, words())?)
+        }
+        Format::R16G16B16 => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, words())?)
+        }
+        Format::R16G16B16A16 => {
+            DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, words())?)
+        }
+        Format::R32G32B32FLOAT => {
+            DynamicImage::ImageRgb32F(ImageBuffer::from_raw(width, height, floats())?)
+        }
+        Format::R32G32B32A32FLOAT => {
+            DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, floats())?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use ultraviolet::{Mat4, Vec3};
+
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::ray::{Intersectable, Ray};
+
+    use super::{model, open};
+
+    /// A triangle lifted 1 up, a camera 5 back and a spot light 3 up pointing down.
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": { "KHR_lights_punctual": { "lights": [
+            { "type": "spot", "intensity": 10, "spot": { "innerConeAngle": 0.2, "outerConeAngle": 0.5 } }
+        ] } },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 1, 2] }],
+        "nodes": [
+            { "camera": 0, "translation": [0, 0, 5] },
+            { "mesh": 0, "translation": [0, 1, 0] },
+            {
+                "extensions": { "KHR_lights_punctual": { "light": 0 } },
+                "translation": [0, 3, 0],
+                "rotation": [-0.70710677, 0, 0, 0.70710677]
+            }
+        ],
+        "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.8, "aspectRatio": 2.0, "znear": 0.1 } }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [0.8, 0.2, 0.1, 1], "metallicFactor": 0 } }],
+        "buffers": [{ "uri": "triangle.bin", "byteLength": 36 }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "accessors": [{
+            "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+            "min": [-1, -1, 0], "max": [1, 1, 0]
+        }]
+    }"#;
+
+    fn write_scene(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let triangle: Vec<u8> = [-1.0_f32, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        std::fs::write(dir.join("triangle.bin"), triangle).unwrap();
+
+        let path = dir.join("scene.gltf");
+        std::fs::write(&path, SCENE).unwrap();
+        path
+    }
+
+    #[test]
+    fn scenes_are_seen_from_the_gltf_camera() {
+        let path = write_scene("gltf-scene-test");
+        let import = open(&path).unwrap();
+        let scene = &import.scene;
+
+        assert_eq!((scene.width(), scene.height()), (1440, 720));
+        assert_eq!(scene.camera.fov, Camera::compute_fov(0.8_f32.to_degrees()));
+        assert_eq!(
+            import.files,
+            vec![path.clone(), path.with_file_name("triangle.bin")]
+        );
+
+        // Up in glTF is -y for us, and the camera looks down -z from the origin.
+        let target = Vec3::new(0.0, -1.0, -5.0);
+        let ray = Ray::new(Vec3::zero(), target.normalized());
+        let hit = scene.world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        assert!((hit.t - target.mag()).abs() < 1e-4);
+        assert_eq!(hit.color(), Color::new(0.8, 0.2, 0.1));
+        assert_eq!(hit.contact.material.id, 0);
+
+        let spot = &scene.world.lights[0];
+        let below = spot.illuminate(Vec3::new(0.0, 0.0, -5.0));
+        assert!((below.direction + Vec3::unit_y()).mag() < 1e-4);
+        assert!((below.distance - 3.0).abs() < 1e-4);
+        assert!(<[f32; 3]>::from(below.radiance)[0] > 0.0);
+        let outside = spot.illuminate(Vec3::new(3.0, 0.0, -5.0));
+        assert_eq!(<[f32; 3]>::from(outside.radiance)[0], 0.0);
+    }
+
+    #[test]
+    fn models_are_turned_y_down_and_moved() {
+        let path = write_scene("gltf-model-test");
+        let transform = Mat4::from_translation(Vec3::new(0.0, 0.0, -10.0));
+        let (group, warnings) = model(&path, transform).unwrap();
+
+        assert_eq!(group.meshes().len(), 1);
+        assert!(warnings.is_empty());
+
+        let top = Vec3::new(0.0, -1.9, -10.0);
+        let hit = group
+            .intersect(&Ray::new(Vec3::zero(), top.normalized()), Ray::UNBOUNDED)
+            .unwrap();
+        assert!((hit.t - top.mag()).abs() < 1e-4);
+
+        let below = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.5, -10.0).normalized());
+        assert!(group.intersect(&below, Ray::UNBOUNDED).is_none());
+    }
+}
@@ -0,0 +1,46 @@
+//! Scenes written for other renderers, converted to ours as closely as our camera, lights and
+//! materials allow. Whatever has no counterpart is skipped with a warning.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+
+use crate::model::mesh::MeshData;
+use crate::scene::Scene;
+use crate::validate::Diagnostic;
+
+pub mod gltf;
+pub mod pbrt;
+pub mod ply;
+pub mod stl;
+
+pub struct Import {
+    pub scene: Scene,
+    /// Parts of the file that were skipped or only approximated.
+    pub warnings: Vec<Diagnostic>,
+    /// Every file the scene was read from, starting with the one that was opened.
+    pub files: Vec<PathBuf>,
+}
+
+/// Imports the scene in `path` if its extension names a format with an importer, or returns
+/// `None` for anything else, such as configs.
+pub fn open(path: &Path) -> Option<anyhow::Result<Import>> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    match extension.as_str() {
+        "gltf" | "glb" => Some(gltf::open(path)),
+        "pbrt" => Some(pbrt::open(path)),
+        _ => None,
+    }
+}
+
+/// Reads the mesh in `path`, a PLY or STL file picked by its extension.
+pub fn mesh(path: &Path) -> anyhow::Result<MeshData> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+
+    match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("ply") => ply::open(path),
+        Some("stl") => stl::open(path),
+        _ => bail!("{} is not a PLY or STL mesh", path.display()),
+    }
+}
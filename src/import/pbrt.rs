@@ -0,0 +1,1023 @@
+//! Importer for a subset of the [pbrt](https://pbrt.org) scene format, as written for pbrt-v3
+//! and pbrt-v4.
+//!
+//! The perspective `Camera` and `Film` resolution, `LookAt` and the other transforms,
+//! `AttributeBegin`/`AttributeEnd`, `Include`, named materials, `sphere`, `trianglemesh` and
+//! `plymesh` shapes, `point`, `distant` and `infinite` lights, and `diffuse`, `conductor` and
+//! `dielectric` materials (along with their pbrt-v3 names) are imported. Our materials have no
+//! refraction, so dielectrics keep only their reflection. Everything else is skipped with a
+//! warning.
+//!
+//! pbrt looks down +z with +y up, while our camera looks down -z with +y down, so the scene is
+//! moved into the space of the pbrt camera and turned around its x axis. Our camera then sits
+//! at the origin, and rolled pbrt cameras keep their roll.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fmt::{self, Display, Formatter};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::vec::IntoIter;
+
+use anyhow::{anyhow, bail, Context};
+use ultraviolet::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::document::{self, Location};
+use crate::import::{ply, Import};
+use crate::light::directional::DirectionalLight;
+use crate::light::point::PointLight;
+use crate::model::mesh::{Mesh, MeshData};
+use crate::model::sphere::Sphere;
+use crate::model::{Material, MaterialIds};
+use crate::scene::Scene;
+use crate::validate::Diagnostic;
+use crate::world::{Sky, World};
+
+pub fn open(path: &Path) -> anyhow::Result<Import> {
+    let mut importer = Importer::new(path);
+    importer.include(path)?;
+    Ok(importer.finish())
+}
+
+/// Imports a scene from `text`, resolving the files it refers to next to `path`.
+pub fn parse(text: &str, path: &Path) -> anyhow::Result<Import> {
+    let mut importer = Importer::new(path);
+    importer.parse(text, path)?;
+    Ok(importer.finish())
+}
+
+struct Importer {
+    world: World,
+    /// Directory that relative file names are resolved against, which pbrt takes from the
+    /// file it was given rather than the one being included.
+    directory: PathBuf,
+    /// Field of view of the shorter image axis, in degrees.
+    fov: f32,
+    resolution: (u32, u32),
+    /// Moves pbrt world space into ours.
+    to_ours: Mat4,
+    graphics: Graphics,
+    stack: Vec<Graphics>,
+    named_materials: HashMap<String, Material>,
+    material_ids: MaterialIds,
+    coordinate_systems: HashMap<String, Mat4>,
+    /// Set inside `ObjectBegin`, whose shapes are only meant to be drawn through instances.
+    in_object: bool,
+    warnings: Vec<Diagnostic>,
+    files: Vec<PathBuf>,
+    /// Files currently being parsed, to catch include cycles.
+    parsing: Vec<PathBuf>,
+}
+
+/// The state saved by `AttributeBegin`.
+#[derive(Clone)]
+struct Graphics {
+    transform: Mat4,
+    material: Material,
+}
+
+impl Importer {
+    fn new(path: &Path) -> Importer {
+        Importer {
+            world: World::builder().build(),
+            directory: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            fov: 90.0,
+            resolution: (1280, 720),
+            to_ours: flip(),
+            graphics: Graphics {
+                transform: Mat4::identity(),
+                material: diffuse(Color::new(0.5, 0.5, 0.5)),
+            },
+            stack: Vec::new(),
+            named_materials: HashMap::new(),
+            material_ids: MaterialIds::default(),
+            coordinate_systems: HashMap::new(),
+            in_object: false,
+            warnings: Vec::new(),
+            files: Vec::new(),
+            parsing: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> Import {
+        let (width, height) = self.resolution;
+
+        // Our field of view is vertical, while pbrt's covers the shorter axis.
+        let fov = if width >= height {
+            self.fov
+        } else {
+            let tan = (self.fov.to_radians() * 0.5).tan() * height as f32 / width as f32;
+            tan.atan().to_degrees() * 2.0
+        };
+
+        Import {
+            scene: Scene::new(Camera::new(width, height).with_fov(fov), self.world),
+            warnings: self.warnings,
+            files: self.files,
+        }
+    }
+
+    fn include(&mut self, path: &Path) -> anyhow::Result<()> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        self.files.push(path.to_path_buf());
+        self.parse(&text, path)
+    }
+
+    fn parse(&mut self, text: &str, path: &Path) -> anyhow::Result<()> {
+        self.parsing.push(document::canonical(path));
+        for directive in directives(text, path)? {
+            self.directive(&directive)?;
+        }
+        self.parsing.pop();
+
+        Ok(())
+    }
+
+    fn warn(&mut self, directive: &Directive, message: impl Into<String>) {
+        let mut warning = Diagnostic::warning("", message);
+        warning.location = Some(directive.location.clone());
+        self.warnings.push(warning);
+    }
+
+    fn concat(&mut self, transform: Mat4) {
+        self.graphics.transform = self.graphics.transform * transform;
+    }
+
+    fn directive(&mut self, directive: &Directive) -> anyhow::Result<()> {
+        match directive.name.as_str() {
+            "Identity" => self.graphics.transform = Mat4::identity(),
+            "Translate" => {
+                let [x, y, z] = directive.numbers()?;
+                self.concat(Mat4::from_translation(Vec3::new(x, y, z)));
+            }
+            "Scale" => {
+                let [x, y, z] = directive.numbers()?;
+                self.concat(Mat4::from_nonuniform_scale(Vec3::new(x, y, z)));
+            }
+            "Rotate" => {
+                let [angle, x, y, z] = directive.numbers()?;
+                let axis = Vec4::new(x, y, z, 0.0).normalized();
+                self.concat(Mat4::from_rotation_around(axis, angle.to_radians()));
+            }
+            "LookAt" => {
+                let [ex, ey, ez, tx, ty, tz, ux, uy, uz] = directive.numbers()?;
+                let eye = Vec3::new(ex, ey, ez);
+                let look_at = look_at(eye, Vec3::new(tx, ty, tz), Vec3::new(ux, uy, uz))
+                    .ok_or_else(|| directive.error("the up vector is parallel to the view"))?;
+                self.concat(look_at);
+            }
+            "Transform" => self.graphics.transform = matrix(directive.numbers()?),
+            "ConcatTransform" => self.concat(matrix(directive.numbers()?)),
+            "CoordinateSystem" => {
+                let name = directive.string()?.to_string();
+                self.coordinate_systems
+                    .insert(name, self.graphics.transform);
+            }
+            "CoordSysTransform" => {
+                let name = directive.string()?;
+                match self.coordinate_systems.get(name) {
+                    Some(&transform) => self.graphics.transform = transform,
+                    None => self.warn(directive, format!("unknown coordinate system `{name}`")),
+                }
+            }
+
+            "Camera" => self.camera(directive)?,
+            "Film" => {
+                let params = &directive.params;
+                let width = params
+                    .float("xresolution")
+                    .unwrap_or(self.resolution.0 as f32);
+                let height = params
+                    .float("yresolution")
+                    .unwrap_or(self.resolution.1 as f32);
+                self.resolution = (width as u32, height as u32);
+            }
+            "WorldBegin" => {
+                self.graphics.transform = Mat4::identity();
+                self.coordinate_systems
+                    .insert("world".into(), Mat4::identity());
+            }
+            "WorldEnd" => {}
+
+            "AttributeBegin" | "TransformBegin" => self.stack.push(self.graphics.clone()),
+            "AttributeEnd" | "TransformEnd" => match self.stack.pop() {
+                Some(graphics) if directive.name == "TransformEnd" => {
+                    self.graphics.transform = graphics.transform
+                }
+                Some(graphics) => self.graphics = graphics,
+                None => self.warn(directive, format!("unmatched `{}`", directive.name)),
+            },
+            "ObjectBegin" => {
+                self.warn(
+                    directive,
+                    "object instances are not supported, so their shapes are skipped",
+                );
+                self.stack.push(self.graphics.clone());
+                self.in_object = true;
+            }
+            "ObjectEnd" => {
+                if let Some(graphics) = self.stack.pop() {
+                    self.graphics = graphics;
+                }
+                self.in_object = false;
+            }
+            // Already warned about at `ObjectBegin`.
+            "ObjectInstance" => {}
+
+            "Material" => {
+                let kind = directive.string()?;
+                self.graphics.material = self.material(directive, kind);
+            }
+            "MakeNamedMaterial" => {
+                let name = directive.string()?.to_string();
+                let kind = directive.params.string("type").unwrap_or_default();
+                let material = self.material(directive, kind);
+                self.named_materials.insert(name, material);
+            }
+            "NamedMaterial" => {
+                let name = directive.string()?;
+                match self.named_materials.get(name) {
+                    Some(material) => self.graphics.material = material.clone(),
+                    None => self.warn(directive, format!("unknown material `{name}`")),
+                }
+            }
+
+            "Shape" => self.shape(directive)?,
+            "LightSource" => self.light(directive),
+            "Include" | "Import" => {
+                let path = self.directory.join(directive.string()?);
+                if self.parsing.contains(&document::canonical(&path)) {
+                    bail!("{}: {} includes itself", directive.location, path.display());
+                }
+                self.include(&path)
+                    .with_context(|| format!("{}: including a file", directive.location))?;
+            }
+
+            "Texture" | "AreaLightSource" | "Sampler" | "Integrator" | "PixelFilter"
+            | "Accelerator" | "ColorSpace" | "Option" | "Attribute" | "MakeNamedMedium"
+            | "MediumInterface" | "ReverseOrientation" | "ActiveTransform" | "TransformTimes" => {
+                self.warn(directive, format!("`{}` is not supported", directive.name))
+            }
+            name => self.warn(directive, format!("unknown directive `{name}`")),
+        }
+
+        Ok(())
+    }
+
+    fn camera(&mut self, directive: &Directive) -> anyhow::Result<()> {
+        let kind = directive.string()?;
+        if kind != "perspective" {
+            self.warn(
+                directive,
+                format!("`{kind}` cameras are not supported, so a perspective camera is used"),
+            );
+        }
+
+        let params = &directive.params;
+        if params
+            .float("lensradius")
+            .is_some_and(|radius| radius > 0.0)
+        {
+            self.warn(directive, "depth of field is not supported");
+        }
+
+        self.fov = params.float("fov").unwrap_or(90.0);
+        self.to_ours = flip() * self.graphics.transform;
+        self.coordinate_systems
+            .insert("camera".into(), self.graphics.transform.inversed());
+
+        Ok(())
+    }
+
+    fn shape(&mut self, directive: &Directive) -> anyhow::Result<()> {
+        if self.in_object {
+            return Ok(());
+        }
+
+        let kind = directive.string()?;
+        let params = &directive.params;
+        let transform = self.to_ours * self.graphics.transform;
+        let mut material = self.graphics.material.clone();
+        self.material_ids.assign(&mut material);
+
+        let mut data = match kind {
+            "sphere" => {
+                let partial = ["zmin", "zmax"]
+                    .iter()
+                    .any(|name| params.get(name).is_some())
+                    || params.float("phimax").is_some_and(|phi| phi < 360.0);
+                if partial {
+                    self.warn(directive, "partial spheres are imported whole");
+                }
+
+                let radius = params.float("radius").unwrap_or(1.0);
+                self.world.entities.push(Box::new(Sphere {
+                    center: transform.transform_point3(Vec3::zero()),
+                    radius: radius * transform.transform_vec3(Vec3::unit_x()).mag(),
+                    material,
+                    ..Sphere::default()
+                }));
+                return Ok(());
+            }
+            "trianglemesh" => {
+                let positions = params.vec3s("P");
+                let mut triangles: Vec<_> = params
+                    .numbers("indices")
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0] as u32, triangle[1] as u32, triangle[2] as u32])
+                    .collect();
+
+                // pbrt-v4 allows leaving out the indices of a single triangle.
+                if triangles.is_empty() && positions.len() == 3 {
+                    triangles.push([0, 1, 2]);
+                }
+
+                let uvs = match params.get("uv") {
+                    Some(_) => params.vec2s("uv"),
+                    None => params.vec2s("st"),
+                };
+
+                MeshData {
+                    positions,
+                    normals: params.vec3s("N"),
+                    uvs,
+                    triangles,
+                    ..MeshData::default()
+                }
+            }
+            "plymesh" => {
+                let name = params
+                    .string("filename")
+                    .ok_or_else(|| directive.error("`plymesh` needs a `filename`"))?;
+                let path = self.directory.join(name);
+
+                if params.get("displacement").is_some() {
+                    self.warn(directive, "displacement is not supported");
+                }
+
+                let data = ply::open(&path)
+                    .with_context(|| format!("{}: loading a mesh", directive.location))?;
+                self.files.push(path);
+                data
+            }
+            _ => {
+                self.warn(directive, format!("`{kind}` shapes are not supported"));
+                return Ok(());
+            }
+        };
+
+        data.transform(transform);
+        let mesh = Mesh::new(data, material)
+            .map_err(|e| directive.error(format!("invalid `{kind}`: {e}")))?;
+
+        if !mesh.data().triangles.is_empty() {
+            self.world.entities.push(Box::new(mesh));
+        }
+
+        Ok(())
+    }
+
+    fn light(&mut self, directive: &Directive) {
+        let Ok(kind) = directive.string() else {
+            return self.warn(directive, "`LightSource` needs a type");
+        };
+
+        let params = &directive.params;
+        let transform = self.to_ours * self.graphics.transform;
+        let scale = params.float("scale").unwrap_or(1.0);
+
+        match kind {
+            "point" => {
+                let from = params.vec3("from").unwrap_or_default();
+                let radiance = self.color(directive, "I").unwrap_or(WHITE) * scale;
+                let (color, intensity) = split(radiance);
+
+                // Our point lights are given in terms of their power.
+                let power = params.float("power").unwrap_or(intensity * 4.0 * PI);
+                self.world.lights.push(Box::new(PointLight::new(
+                    transform.transform_point3(from),
+                    color,
+                    power,
+                )));
+            }
+            "distant" => {
+                let from = params.vec3("from").unwrap_or_default();
+                let to = params.vec3("to").unwrap_or(Vec3::unit_z());
+                let radiance = self.color(directive, "L").unwrap_or(WHITE) * scale;
+                let (color, intensity) = split(radiance);
+
+                let intensity = params.float("illuminance").unwrap_or(intensity);
+                self.world.lights.push(Box::new(DirectionalLight::new(
+                    transform.transform_vec3(to - from),
+                    color,
+                    intensity,
+                )));
+            }
+            "infinite" => {
+                if params.get("filename").is_some() {
+                    return self.warn(directive, "environment maps are not supported");
+                }
+
+                let radiance = self.color(directive, "L").unwrap_or(WHITE) * scale;
+                self.world.sky = Sky {
+                    from: radiance,
+                    to: radiance,
+                };
+            }
+            _ => self.warn(directive, format!("`{kind}` lights are not supported")),
+        }
+    }
+
+    fn material(&mut self, directive: &Directive, kind: &str) -> Material {
+        let params = &directive.params;
+        let roughness = params.float("roughness").or_else(|| {
+            let u = params.float("uroughness")?;
+            Some((u + params.float("vroughness").unwrap_or(u)) * 0.5)
+        });
+
+        match kind {
+            "diffuse" | "matte" => diffuse(self.reflectance(directive, "Kd")),
+            "conductor" | "metal" => {
+                let color = self
+                    .color(directive, "reflectance")
+                    .or_else(|| params.string("eta").and_then(metal))
+                    .or_else(|| {
+                        let eta = self.color(directive, "eta")?;
+                        let k = self.color(directive, "k")?;
+                        Some(conductor_reflectance(eta, k))
+                    })
+                    .unwrap_or(COPPER);
+
+                let roughness = roughness.unwrap_or(if kind == "metal" { 0.01 } else { 0.0 });
+                diffuse(color).reflective(average(color), roughness)
+            }
+            "mirror" => {
+                let color = self
+                    .color(directive, "Kr")
+                    .unwrap_or(Color::new(0.9, 0.9, 0.9));
+                diffuse(color).reflective(average(color), 0.0)
+            }
+            "dielectric" | "glass" | "thindielectric" => {
+                self.warn(
+                    directive,
+                    format!("refraction is not supported, so `{kind}` only reflects"),
+                );
+
+                let eta = params.float("eta").unwrap_or(1.5);
+                let reflectivity = ((eta - 1.0) / (eta + 1.0)).powi(2);
+                Material::new(WHITE, 0.0).reflective(reflectivity, roughness.unwrap_or(0.0))
+            }
+            _ => {
+                self.warn(
+                    directive,
+                    format!("`{kind}` materials are not supported, so it is imported as diffuse"),
+                );
+                diffuse(self.reflectance(directive, "Kd"))
+            }
+        }
+    }
+
+    /// The diffuse color of a material, written as `reflectance` in pbrt-v4 and as `v3_name`
+    /// in pbrt-v3.
+    fn reflectance(&mut self, directive: &Directive, v3_name: &str) -> Color {
+        self.color(directive, "reflectance")
+            .or_else(|| self.color(directive, v3_name))
+            .unwrap_or(Color::new(0.5, 0.5, 0.5))
+    }
+
+    /// Reads an RGB or constant parameter, warning about spectra and textures we cannot use.
+    fn color(&mut self, directive: &Directive, name: &str) -> Option<Color> {
+        let param = directive.params.get(name)?;
+
+        match (param.kind.as_str(), param.numbers().as_slice()) {
+            ("rgb" | "color", &[r, g, b]) => Some(Color::new(r, g, b)),
+            ("float" | "spectrum", &[value]) => Some(Color::new(value, value, value)),
+            ("texture", _) => {
+                self.warn(
+                    directive,
+                    format!("textures are not supported for `{name}`"),
+                );
+                None
+            }
+            (kind, _) => {
+                self.warn(
+                    directive,
+                    format!("`{kind}` values are not supported for `{name}`"),
+                );
+                None
+            }
+        }
+    }
+}
+
+const WHITE: Color = Color::new(1.0, 1.0, 1.0);
+
+/// Reflectance of copper at normal incidence, the default pbrt-v4 conductor.
+const COPPER: Color = Color::new(0.955, 0.638, 0.538);
+
+fn diffuse(color: Color) -> Material {
+    Material::new(color, 1.0)
+}
+
+/// Reflectance at normal incidence of the metals named by pbrt's built-in spectra.
+fn metal(eta: &str) -> Option<Color> {
+    Some(match eta {
+        "metal-Ag-eta" => Color::new(0.972, 0.960, 0.915),
+        "metal-Al-eta" => Color::new(0.913, 0.922, 0.924),
+        "metal-Au-eta" => Color::new(1.000, 0.766, 0.336),
+        "metal-Cu-eta" => COPPER,
+        _ => return None,
+    })
+}
+
+/// Fresnel reflectance at normal incidence of a conductor with the given refractive index
+/// and absorption.
+fn conductor_reflectance(eta: Color, k: Color) -> Color {
+    let [eta, k] = [eta, k].map(<[f32; 3]>::from);
+    Color::from(std::array::from_fn::<f32, 3, _>(|i| {
+        let k_sq = k[i] * k[i];
+        ((eta[i] - 1.0).powi(2) + k_sq) / ((eta[i] + 1.0).powi(2) + k_sq)
+    }))
+}
+
+fn average(color: Color) -> f32 {
+    <[f32; 3]>::from(color).iter().sum::<f32>() / 3.0
+}
+
+/// Splits a radiance into a color whose brightest channel is 1 and its intensity.
+fn split(radiance: Color) -> (Color, f32) {
+    let intensity = <[f32; 3]>::from(radiance).into_iter().fold(0.0, f32::max);
+
+    if intensity <= 0.0 {
+        return (WHITE, 0.0);
+    }
+
+    (radiance * (1.0 / intensity), intensity)
+}
+
+/// Turns pbrt camera space, looking down +z with +y up, into ours.
+fn flip() -> Mat4 {
+    Mat4::from_nonuniform_scale(Vec3::new(1.0, -1.0, -1.0))
+}
+
+/// The camera transform of pbrt's `LookAt`, or `None` if `up` is along the view.
+fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Option<Mat4> {
+    let direction = (target - eye).normalized();
+    let right = up.normalized().cross(direction);
+
+    if right.mag_sq() < 1e-12 {
+        return None;
+    }
+
+    let right = right.normalized();
+    let up = direction.cross(right);
+
+    let camera_to_world = Mat4::new(
+        right.into_homogeneous_vector(),
+        up.into_homogeneous_vector(),
+        direction.into_homogeneous_vector(),
+        eye.into_homogeneous_point(),
+    );
+
+    Some(camera_to_world.inversed())
+}
+
+/// A matrix written by `Transform` and `ConcatTransform`, one column after another.
+fn matrix(values: [f32; 16]) -> Mat4 {
+    let column = |i: usize| Vec4::new(values[i], values[i + 1], values[i + 2], values[i + 3]);
+    Mat4::new(column(0), column(4), column(8), column(12))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(f32),
+    Open,
+    Close,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{word}`"),
+            Token::Text(text) => write!(f, "\"{text}\""),
+            Token::Number(number) => write!(f, "{number}"),
+            Token::Open => write!(f, "`[`"),
+            Token::Close => write!(f, "`]`"),
+        }
+    }
+}
+
+struct Spanned {
+    token: Token,
+    location: Location,
+}
+
+fn tokenize(text: &str, path: &Path) -> anyhow::Result<Vec<Spanned>> {
+    let mut chars = text.chars().peekable();
+    let mut tokens = Vec::new();
+    let (mut line, mut column) = (1, 1);
+
+    while let Some(&c) = chars.peek() {
+        let location = Location {
+            file: path.to_path_buf(),
+            line,
+            column,
+        };
+
+        let mut taken = String::new();
+        let mut take_while = |chars: &mut Peekable<_>, keep: &dyn Fn(char) -> bool| {
+            taken.clear();
+            while let Some(c) = chars.next_if(|&c| keep(c)) {
+                taken.push(c);
+            }
+            taken.clone()
+        };
+
+        let token = match c {
+            '\n' => {
+                chars.next();
+                line += 1;
+                column = 1;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                column += 1;
+                continue;
+            }
+            '#' => {
+                take_while(&mut chars, &|c| c != '\n');
+                continue;
+            }
+            '[' | ']' => {
+                chars.next();
+                if c == '[' {
+                    Token::Open
+                } else {
+                    Token::Close
+                }
+            }
+            '"' => {
+                chars.next();
+                let text = take_while(&mut chars, &|c| c != '"' && c != '\n');
+                if chars.next() != Some('"') {
+                    bail!("{location}: unterminated string");
+                }
+                column += 2;
+                Token::Text(text)
+            }
+            c if c.is_ascii_digit() || "+-.".contains(c) => {
+                let number = take_while(&mut chars, &|c| {
+                    c.is_ascii_alphanumeric() || "+-.".contains(c)
+                });
+                let value = number
+                    .parse()
+                    .map_err(|_| anyhow!("{location}: invalid number `{number}`"))?;
+                Token::Number(value)
+            }
+            c if c.is_ascii_alphabetic() => Token::Word(take_while(&mut chars, &|c| {
+                c.is_ascii_alphanumeric() || c == '_'
+            })),
+            c => bail!("{location}: unexpected `{c}`"),
+        };
+
+        column += match &token {
+            Token::Open | Token::Close => 1,
+            Token::Text(text) => text.chars().count(),
+            Token::Word(word) => word.len(),
+            Token::Number(_) => taken.chars().count(),
+        };
+
+        tokens.push(Spanned { token, location });
+    }
+
+    Ok(tokens)
+}
+
+/// One directive, such as `Shape "sphere" "float radius" [2]`.
+struct Directive {
+    name: String,
+    location: Location,
+    /// Names given before the parameters, such as the kind of shape.
+    strings: Vec<String>,
+    /// Numbers given before the parameters, such as the offset of `Translate`.
+    numbers: Vec<f32>,
+    params: Params,
+}
+
+impl Directive {
+    fn error(&self, message: impl Display) -> anyhow::Error {
+        anyhow!("{}: {message}", self.location)
+    }
+
+    fn string(&self) -> anyhow::Result<&str> {
+        self.strings
+            .first()
+            .map(String::as_str)
+            .ok_or_else(|| self.error(format!("`{}` needs a name", self.name)))
+    }
+
+    fn numbers<const N: usize>(&self) -> anyhow::Result<[f32; N]> {
+        self.numbers.as_slice().try_into().map_err(|_| {
+            self.error(format!(
+                "`{}` takes {N} numbers, not {}",
+                self.name,
+                self.numbers.len()
+            ))
+        })
+    }
+}
+
+/// How many names a directive takes before its parameters.
+fn names(directive: &str) -> usize {
+    match directive {
+        "Texture" => 3,
+        "MediumInterface" => 2,
+        "Camera" | "Film" | "Sampler" | "Integrator" | "PixelFilter" | "Accelerator"
+        | "ColorSpace" | "Shape" | "Material" | "MakeNamedMaterial" | "NamedMaterial"
+        | "LightSource" | "AreaLightSource" | "Include" | "Import" | "CoordinateSystem"
+        | "CoordSysTransform" | "ObjectBegin" | "ObjectInstance" | "MakeNamedMedium"
+        | "Attribute" => 1,
+        _ => 0,
+    }
+}
+
+fn directives(text: &str, path: &Path) -> anyhow::Result<Vec<Directive>> {
+    let mut tokens = tokenize(text, path)?.into_iter().peekable();
+    let mut directives = Vec::new();
+
+    while let Some(Spanned { token, location }) = tokens.next() {
+        let Token::Word(name) = token else {
+            bail!("{location}: expected a directive, found {token}");
+        };
+
+        // The only directive taking a bare word, such as `ActiveTransform All`.
+        if name == "ActiveTransform" {
+            tokens.next_if(|next| matches!(next.token, Token::Word(_)));
+        }
+
+        let mut directive = Directive {
+            strings: Vec::new(),
+            numbers: Vec::new(),
+            params: Params::default(),
+            name,
+            location,
+        };
+
+        while let Some(next) = tokens.peek() {
+            match &next.token {
+                Token::Word(word) if !is_bool(word) => break,
+                Token::Text(text) if directive.strings.len() < names(&directive.name) => {
+                    directive.strings.push(text.clone());
+                    tokens.next();
+                }
+                Token::Number(_) | Token::Open if directive.params.0.is_empty() => {
+                    for value in values(&mut tokens)? {
+                        match value {
+                            Value::Number(number) => directive.numbers.push(number),
+                            _ => bail!("{}: expected a number", directive.location),
+                        }
+                    }
+                }
+                Token::Text(declaration) => {
+                    let location = next.location.clone();
+                    let Some((kind, name)) = declaration.split_once(char::is_whitespace) else {
+                        bail!("{location}: expected a parameter such as \"float radius\"");
+                    };
+
+                    let kind = kind.to_string();
+                    let name = name.trim().to_string();
+                    tokens.next();
+
+                    let values = values(&mut tokens)
+                        .with_context(|| format!("{location}: reading `{name}`"))?;
+                    directive.params.0.insert(name, Param { kind, values });
+                }
+                token => bail!("{}: unexpected {token}", next.location),
+            }
+        }
+
+        directives.push(directive);
+    }
+
+    Ok(directives)
+}
+
+fn is_bool(word: &str) -> bool {
+    word == "true" || word == "false"
+}
+
+/// Reads a single value, or a list of them in brackets.
+fn values(tokens: &mut Peekable<IntoIter<Spanned>>) -> anyhow::Result<Vec<Value>> {
+    let value = |token: Token| match token {
+        Token::Number(number) => Ok(Value::Number(number)),
+        Token::Text(text) => Ok(Value::Text(text)),
+        Token::Word(word) if is_bool(&word) => Ok(Value::Bool(word == "true")),
+        token => Err(anyhow!("unexpected {token}")),
+    };
+
+    let Some(Spanned { token, location }) = tokens.next() else {
+        bail!("expected a value");
+    };
+
+    if token != Token::Open {
+        return Ok(vec![value(token)?]);
+    }
+
+    let mut values = Vec::new();
+    loop {
+        match tokens.next() {
+            Some(Spanned {
+                token: Token::Close,
+                ..
+            }) => return Ok(values),
+            Some(Spanned { token, location }) => {
+                values.push(value(token).map_err(|e| anyhow!("{location}: {e}"))?)
+            }
+            None => bail!("{location}: unclosed `[`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f32),
+    Text(String),
+    Bool(bool),
+}
+
+/// A parameter such as `"float radius" [2]`.
+struct Param {
+    kind: String,
+    values: Vec<Value>,
+}
+
+impl Param {
+    fn numbers(&self) -> Vec<f32> {
+        self.values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Number(number) => Some(*number),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Params(HashMap<String, Param>);
+
+impl Params {
+    fn get(&self, name: &str) -> Option<&Param> {
+        self.0.get(name)
+    }
+
+    fn numbers(&self, name: &str) -> Vec<f32> {
+        self.get(name).map(Param::numbers).unwrap_or_default()
+    }
+
+    fn float(&self, name: &str) -> Option<f32> {
+        let param = self.get(name)?;
+        matches!(param.kind.as_str(), "float" | "integer")
+            .then(|| param.numbers().first().copied())
+            .flatten()
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)?.values.first()? {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn vec3(&self, name: &str) -> Option<Vec3> {
+        self.vec3s(name).first().copied()
+    }
+
+    fn vec3s(&self, name: &str) -> Vec<Vec3> {
+        self.numbers(name)
+            .chunks_exact(3)
+            .map(|v| Vec3::new(v[0], v[1], v[2]))
+            .collect()
+    }
+
+    fn vec2s(&self, name: &str) -> Vec<Vec2> {
+        self.numbers(name)
+            .chunks_exact(2)
+            .map(|v| Vec2::new(v[0], v[1]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ultraviolet::Vec3;
+
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::ray::{Intersectable, Ray};
+    use crate::SurfaceType;
+
+    use super::{open, parse};
+
+    const SCENE: &str = r#"
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" [45]
+Film "rgb" "integer xresolution" [64] "integer yresolution" [48]
+Sampler "halton" "integer pixelsamples" 16
+WorldBegin
+LightSource "point" "rgb I" [10 5 5] "point3 from" [0 4 -4]
+LightSource "distant" "point3 from" [0 0 0] "point3 to" [0 -1 0] "rgb L" [2 2 2]
+AttributeBegin
+  Material "conductor" "float roughness" 0.1
+  Translate 0 2 0
+  Shape "sphere" "float radius" 0.5
+AttributeEnd
+Material "diffuse" "rgb reflectance" [0.2 0.4 0.6]
+Shape "trianglemesh" "point3 P" [-1 -1 0  1 -1 0  1 1 0  -1 1 0]
+    "integer indices" [0 1 2  0 2 3]
+Shape "cylinder" # not supported
+"#;
+
+    #[test]
+    fn scenes_are_seen_from_the_pbrt_camera() {
+        let import = parse(SCENE, Path::new("scene.pbrt")).unwrap();
+        let scene = &import.scene;
+
+        assert_eq!((scene.width(), scene.height()), (64, 48));
+        assert_eq!(scene.camera.fov, Camera::compute_fov(45.0));
+        assert_eq!(scene.world.entities.len(), 2);
+
+        let lines: Vec<_> = import
+            .warnings
+            .iter()
+            .map(|warning| warning.location.as_ref().unwrap().line)
+            .collect();
+        assert_eq!(lines, vec![5, 17]);
+
+        // Up in pbrt is -y for us, and the camera looks down -z from the origin.
+        let sphere = Vec3::new(0.0, -2.0, -5.0);
+        let ray = Ray::new(Vec3::zero(), sphere.normalized());
+        let hit = scene.world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        assert!((hit.t - (sphere.mag() - 0.5)).abs() < 1e-3);
+        assert!(matches!(
+            hit.contact.material.surface,
+            SurfaceType::Reflective { roughness, .. } if roughness == 0.1
+        ));
+
+        let ray = Ray::new(Vec3::zero(), -Vec3::unit_z());
+        let hit = scene.world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-4);
+        assert_eq!(hit.entity, 1);
+        assert_eq!(hit.color(), Color::new(0.2, 0.4, 0.6));
+
+        let point = scene.world.lights[0].illuminate(Vec3::zero());
+        assert!((point.direction - Vec3::new(0.0, -4.0, -1.0).normalized()).mag() < 1e-4);
+        let expected = Color::new(1.0, 0.5, 0.5) * (10.0 / 17.0);
+        assert!(<[f32; 3]>::from(point.radiance - expected)
+            .iter()
+            .all(|channel| channel.abs() < 1e-5));
+
+        let distant = scene.world.lights[1].illuminate(Vec3::zero());
+        assert!((distant.direction + Vec3::unit_y()).mag() < 1e-4);
+        assert_eq!(distant.radiance, Color::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_their_location() {
+        let error = parse(
+            "WorldBegin\nShape \"sphere\" \"float radius\" [1",
+            Path::new("a.pbrt"),
+        );
+        assert!(format!("{:#}", error.err().unwrap()).contains("a.pbrt:2:31"));
+
+        let error = parse("Translate 1 2", Path::new("a.pbrt"));
+        assert!(format!("{:#}", error.err().unwrap()).starts_with("a.pbrt:1:1"));
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let dir = std::env::temp_dir().join(format!("pbrt-include-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.pbrt"), "WorldBegin\nInclude \"b.pbrt\"\n").unwrap();
+        std::fs::write(dir.join("b.pbrt"), "\nInclude \"a.pbrt\"\n").unwrap();
+        std::fs::write(dir.join("self.pbrt"), "WorldBegin\nInclude \"self.pbrt\"\n").unwrap();
+
+        let error = open(&dir.join("self.pbrt")).err().unwrap();
+        assert!(format!("{error:#}").contains("self.pbrt:2:1"));
+        assert!(format!("{error:#}").contains("includes itself"));
+
+        let error = open(&dir.join("a.pbrt")).err().unwrap();
+        assert!(format!("{error:#}").contains("b.pbrt:2:1"));
+        assert!(format!("{error:#}").contains("includes itself"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+//! Reader for [PLY](https://paulbourke.net/dataformats/ply/) meshes, in ASCII or binary.
+
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+use ultraviolet::{Vec2, Vec3};
+
+use crate::color::{Color, ColorSpace};
+use crate::model::mesh::MeshData;
+
+pub fn open(path: &Path) -> anyhow::Result<MeshData> {
+    let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    parse(&bytes).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Reads the vertex positions, normals, texture coordinates and colors and the faces of a PLY
+/// file, splitting polygons into triangles. Other elements and properties are skipped.
+pub fn parse(bytes: &[u8]) -> anyhow::Result<MeshData> {
+    const END: &[u8] = b"end_header";
+
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .context("the header has no `end_header`")?;
+    let body = bytes[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |newline| end + newline + 1);
+
+    let (encoding, elements) = header(&String::from_utf8_lossy(&bytes[..end]))?;
+    let mut values = match encoding {
+        Encoding::Ascii => {
+            let text = std::str::from_utf8(&bytes[body..]).context("the body is not text")?;
+            Values::Ascii(text)
+        }
+        Encoding::Binary { little_endian } => Values::Binary {
+            bytes: &bytes[body..],
+            little_endian,
+        },
+    };
+
+    let mut data = MeshData::default();
+    for element in &elements {
+        element.read(&mut values, &mut data)?;
+    }
+
+    data.validate()?;
+    Ok(data)
+}
+
+enum Encoding {
+    Ascii,
+    Binary { little_endian: bool },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> anyhow::Result<Scalar> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => bail!("unknown property type `{name}`"),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    kind: Scalar,
+    /// Type of the length that precedes the values of a list property.
+    list: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn header(text: &str) -> anyhow::Result<(Encoding, Vec<Element>)> {
+    let mut lines = text.lines().map(str::trim);
+    ensure!(lines.next() == Some("ply"), "not a PLY file");
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for line in lines {
+        let words: Vec<_> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            [] | ["comment" | "obj_info", ..] => {}
+            ["format", format, _version] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::Binary {
+                        little_endian: true,
+                    },
+                    "binary_big_endian" => Encoding::Binary {
+                        little_endian: false,
+                    },
+                    _ => bail!("unknown format `{format}`"),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().context("invalid element count")?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, kind, name] => {
+                let element = elements.last_mut().context("property outside an element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind)?,
+                    list: Some(Scalar::parse(count)?),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements.last_mut().context("property outside an element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind)?,
+                    list: None,
+                });
+            }
+            _ => bail!("unexpected header line `{line}`"),
+        }
+    }
+
+    Ok((encoding.context("the header has no format")?, elements))
+}
+
+impl Element {
+    /// Decodes a color channel, stored either as an integer over its whole range or as a
+    /// float from 0 to 1, from sRGB.
+    fn channel(&self, index: usize, value: f64) -> f32 {
+        let value = match self.properties[index].kind {
+            Scalar::U8 => value / u8::MAX as f64,
+            Scalar::U16 => value / u16::MAX as f64,
+            _ => value,
+        };
+        ColorSpace::Srgb.decode(value as f32)
+    }
+
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    }
+
+    fn read(&self, values: &mut Values, data: &mut MeshData) -> anyhow::Result<()> {
+        let position = [self.find(&["x"]), self.find(&["y"]), self.find(&["z"])];
+        let normal = [self.find(&["nx"]), self.find(&["ny"]), self.find(&["nz"])];
+        let uv = [
+            self.find(&["u", "s", "texture_u", "texture_s"]),
+            self.find(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let color = [
+            self.find(&["red", "diffuse_red"]),
+            self.find(&["green", "diffuse_green"]),
+            self.find(&["blue", "diffuse_blue"]),
+        ];
+        let indices = self.find(&["vertex_indices", "vertex_index"]);
+
+        let is_vertex = self.name == "vertex";
+        let is_face = self.name == "face";
+        ensure!(
+            !is_vertex || position.iter().all(Option::is_some),
+            "vertices have no position"
+        );
+        ensure!(
+            !is_face || indices.is_some(),
+            "faces have no vertex indices"
+        );
+
+        let mut row = vec![Vec::new(); self.properties.len()];
+
+        for _ in 0..self.count {
+            for (property, values_of) in self.properties.iter().zip(&mut row) {
+                let count = match property.list {
+                    Some(kind) => {
+                        let count = values.next(kind)?;
+                        ensure!(
+                            count >= 0.0
+                                && count.fract() == 0.0
+                                && count <= values.left(property.kind) as f64,
+                            "invalid list length {count}"
+                        );
+                        count as usize
+                    }
+                    None => 1,
+                };
+
+                values_of.clear();
+                for _ in 0..count {
+                    values_of.push(values.next(property.kind)?);
+                }
+            }
+
+            let get = |index: Option<usize>| index.map(|index| row[index][0] as f32);
+
+            if is_vertex {
+                let [x, y, z] = position.map(get);
+                data.positions
+                    .push(Vec3::new(x.unwrap(), y.unwrap(), z.unwrap()));
+
+                if let [Some(x), Some(y), Some(z)] = normal.map(get) {
+                    data.normals.push(Vec3::new(x, y, z));
+                }
+
+                if let [Some(u), Some(v)] = uv.map(get) {
+                    data.uvs.push(Vec2::new(u, v));
+                }
+
+                let channel =
+                    |index: Option<usize>| index.map(|index| self.channel(index, row[index][0]));
+                if let [Some(r), Some(g), Some(b)] = color.map(channel) {
+                    data.colors.push(Color::new(r, g, b));
+                }
+            } else if is_face {
+                let polygon = &row[indices.unwrap()];
+                for i in 1..polygon.len().saturating_sub(1) {
+                    data.triangles
+                        .push([polygon[0], polygon[i], polygon[i + 1]].map(|index| index as u32));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum Values<'a> {
+    /// The text left to read.
+    Ascii(&'a str),
+    Binary {
+        bytes: &'a [u8],
+        little_endian: bool,
+    },
+}
+
+impl Values<'_> {
+    fn next(&mut self, kind: Scalar) -> anyhow::Result<f64> {
+        match self {
+            Values::Ascii(text) => {
+                let rest = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+                let end = rest
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(rest.len());
+                let (word, tail) = rest.split_at(end);
+                *text = tail;
+
+                ensure!(!word.is_empty(), "unexpected end of file");
+                word.parse()
+                    .with_context(|| format!("invalid number `{word}`"))
+            }
+            Values::Binary {
+                bytes,
+                little_endian,
+            } => {
+                let size = kind.size();
+                ensure!(bytes.len() >= size, "unexpected end of file");
+
+                let mut buffer = [0; 8];
+                buffer[..size].copy_from_slice(&bytes[..size]);
+                *bytes = &bytes[size..];
+
+                if !*little_endian {
+                    buffer[..size].reverse();
+                }
+
+                let [a, b, c, d, ..] = buffer;
+                Ok(match kind {
+                    Scalar::I8 => a as i8 as f64,
+                    Scalar::U8 => a as f64,
+                    Scalar::I16 => i16::from_le_bytes([a, b]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([a, b]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([a, b, c, d]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([a, b, c, d]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([a, b, c, d]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    /// Most values of `kind` that can be left, since each takes at least one byte of text.
+    fn left(&self, kind: Scalar) -> usize {
+        match self {
+            Values::Ascii(text) => text.len(),
+            Values::Binary { bytes, .. } => bytes.len() / kind.size(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::{Vec2, Vec3};
+
+    use crate::color::Color;
+
+    use super::parse;
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float u
+property float v
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+1 1 0 0 0 1 1 1
+0 1 0 0 0 1 0 1
+4 0 1 2 3
+";
+
+    fn binary(little_endian: bool) -> Vec<u8> {
+        let format = if little_endian {
+            "binary_little_endian"
+        } else {
+            "binary_big_endian"
+        };
+        let header = ASCII
+            .split("end_header\n")
+            .next()
+            .unwrap()
+            .replace("ascii", format);
+
+        let mut bytes = format!("{header}end_header\n").into_bytes();
+        let float = |value: f32| {
+            if little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+        };
+
+        for [x, y] in [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]] {
+            for value in [x, y, 0.0, 0.0, 0.0, 1.0, x, y] {
+                bytes.extend(float(value));
+            }
+        }
+
+        bytes.push(4);
+        for index in 0..4_i32 {
+            let index = if little_endian {
+                index.to_le_bytes()
+            } else {
+                index.to_be_bytes()
+            };
+            bytes.extend(index);
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn ascii_and_binary_files_read_the_same() {
+        let ascii = parse(ASCII.as_bytes()).unwrap();
+
+        assert_eq!(ascii.positions[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(ascii.normals, vec![Vec3::unit_z(); 4]);
+        assert_eq!(ascii.uvs[3], Vec2::new(0.0, 1.0));
+        assert_eq!(ascii.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+
+        assert_eq!(parse(&binary(true)).unwrap(), ascii);
+        assert_eq!(parse(&binary(false)).unwrap(), ascii);
+    }
+
+    #[test]
+    fn vertex_colors_are_decoded_from_srgb() {
+        let text = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+0 1 0 0 0 128
+3 0 1 2
+";
+        let data = parse(text.as_bytes()).unwrap();
+
+        assert_eq!(data.colors[0], Color::new(1.0, 0.0, 0.0));
+        assert_eq!(data.colors[1], Color::new(0.0, 1.0, 0.0));
+        assert_eq!(data.colors[2], Color::from_hex(0x000080));
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let bytes = binary(true);
+        assert!(parse(&bytes[..bytes.len() - 2]).is_err());
+        assert!(parse(ASCII.replace("4 0 1 2 3", "4 0 1 2 7").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn list_lengths_must_fit_the_file() {
+        for length in ["1e12", "-1", "2.5"] {
+            let text = ASCII.replace("4 0 1 2 3", &format!("{length} 0 1 2 3"));
+            let error = parse(text.as_bytes()).err().unwrap();
+            assert!(
+                format!("{error:#}").contains("invalid list length"),
+                "{length}"
+            );
+        }
+
+        let mut bytes = binary(true);
+        let face = bytes.len() - 17;
+        assert_eq!(bytes[face], 4);
+        bytes[face] = 255;
+        assert!(format!("{:#}", parse(&bytes).err().unwrap()).contains("invalid list length"));
+    }
+}
@@ -0,0 +1,165 @@
+//! Reader for [STL](https://en.wikipedia.org/wiki/STL_(file_format)) meshes, in ASCII or
+//! binary.
+//!
+//! Every STL triangle has its own three corners, so meshes read from it are shaded flat. The
+//! stored facet normals are skipped, since the corners give the same ones.
+
+use std::path::Path;
+
+use anyhow::{ensure, Context};
+use ultraviolet::Vec3;
+
+use crate::model::mesh::MeshData;
+
+const HEADER: usize = 80;
+const FACET: usize = 50;
+
+pub fn open(path: &Path) -> anyhow::Result<MeshData> {
+    let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    parse(&bytes).with_context(|| format!("reading {}", path.display()))
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<MeshData> {
+    // Binary files may start with `solid` as well, but only they match their facet count.
+    let positions = if bytes.starts_with(b"solid") && facets(bytes).is_none() {
+        let text = std::str::from_utf8(bytes)
+            .context("the file is neither text nor binary with as many facets as it says")?;
+        ascii(text)?
+    } else {
+        binary(bytes)?
+    };
+
+    let triangles = (0..positions.len() as u32 / 3)
+        .map(|triangle| [0, 1, 2].map(|corner| triangle * 3 + corner))
+        .collect();
+
+    Ok(MeshData {
+        positions,
+        triangles,
+        ..MeshData::default()
+    })
+}
+
+/// The number of facets of a binary file, if its size matches.
+fn facets(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(HEADER..HEADER + 4)?;
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+    (bytes.len() == HEADER + 4 + count * FACET).then_some(count)
+}
+
+fn binary(bytes: &[u8]) -> anyhow::Result<Vec<Vec3>> {
+    ensure!(bytes.len() >= HEADER + 4, "the header is truncated");
+    let count = facets(bytes).context("the file size does not match its facet count")?;
+
+    let float = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let mut positions = Vec::with_capacity(count * 3);
+
+    for facet in 0..count {
+        // Each facet is a normal, three corners and a 2 byte attribute count.
+        let start = HEADER + 4 + facet * FACET + 12;
+        for corner in 0..3 {
+            let offset = start + corner * 12;
+            positions.push(Vec3::new(
+                float(offset),
+                float(offset + 4),
+                float(offset + 8),
+            ));
+        }
+    }
+
+    Ok(positions)
+}
+
+fn ascii(text: &str) -> anyhow::Result<Vec<Vec3>> {
+    let mut words = text.split_ascii_whitespace();
+    let mut positions = Vec::new();
+
+    while let Some(word) = words.next() {
+        if word != "vertex" {
+            continue;
+        }
+
+        let mut coordinate = || -> anyhow::Result<f32> {
+            let word = words.next().context("unexpected end of file")?;
+            word.parse()
+                .with_context(|| format!("invalid number `{word}`"))
+        };
+        positions.push(Vec3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+
+    ensure!(
+        positions.len() % 3 == 0,
+        "{} vertices do not make whole triangles",
+        positions.len()
+    );
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use super::parse;
+
+    const ASCII: &str = "solid quad
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid quad
+";
+
+    fn binary() -> Vec<u8> {
+        // Binary headers are free text, and exporters often start them with `solid` too.
+        let mut bytes = b"solid quad".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(2_u32.to_le_bytes());
+
+        for triangle in [
+            [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]],
+            [[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+        ] {
+            for value in [0.0_f32, 0.0, 1.0] {
+                bytes.extend(value.to_le_bytes());
+            }
+            for [x, y] in triangle {
+                for value in [x, y, 0.0_f32] {
+                    bytes.extend(value.to_le_bytes());
+                }
+            }
+            bytes.extend([0, 0]);
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn ascii_and_binary_files_read_the_same() {
+        let ascii = parse(ASCII.as_bytes()).unwrap();
+
+        assert_eq!(ascii.positions.len(), 6);
+        assert_eq!(ascii.positions[5], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(ascii.triangles, vec![[0, 1, 2], [3, 4, 5]]);
+        assert!(ascii.normals.is_empty());
+
+        assert_eq!(parse(&binary()).unwrap(), ascii);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let bytes = binary();
+        assert!(parse(&bytes[..bytes.len() - 2]).is_err());
+        assert!(parse(ASCII.replace("vertex 0 1 0", "").as_bytes()).is_err());
+        assert!(parse(ASCII.replace("vertex 0 1 0", "vertex 0 x 0").as_bytes()).is_err());
+    }
+}
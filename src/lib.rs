@@ -0,0 +1,50 @@
+//! A CPU raytracer loosely based on "Ray Tracing in One Weekend".
+//!
+//! Scenes are either loaded from a config file with [`serialize::parse_config`] or assembled
+//! in code, and rendered into memory without needing a window:
+//!
+//! ```
+//! use raytracer::light::point::PointLight;
+//! use raytracer::{render, Camera, Color, Material, RenderSettings, Scene, World};
+//! use ultraviolet::Vec3;
+//!
+//! let world = World::builder()
+//!     .sphere(Vec3::new(0.0, 0.0, -3.0), 1.0, Material::new(Color::new(0.8, 0.1, 0.1), 0.8))
+//!     .light(PointLight::new(Vec3::new(2.0, -2.0, 0.0), Color::new(1.0, 1.0, 1.0), 100.0))
+//!     .build();
+//!
+//! let scene = Scene::new(Camera::new(64, 48), world);
+//! let image = render(&scene, &RenderSettings::default());
+//! assert_eq!(image.dimensions(), (64, 48));
+//! ```
+
+pub mod animation;
+pub mod aov;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod denoise;
+pub mod document;
+pub mod film;
+pub mod format;
+pub mod import;
+pub mod light;
+pub mod model;
+pub mod output;
+pub mod ray;
+pub mod registry;
+mod sampler;
+pub mod scene;
+pub mod serialize;
+pub mod stats;
+pub mod texture;
+pub mod validate;
+pub mod volume;
+pub mod world;
+
+pub use camera::Camera;
+pub use color::Color;
+pub use film::{Framebuffer, ToneMapping};
+pub use model::{Material, SurfaceType};
+pub use scene::{render, render_buffer, Image, RenderSettings, Scene};
+pub use world::{World, WorldBuilder};
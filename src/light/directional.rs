@@ -1,30 +1,39 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use ultraviolet::Vec3;
 
 use crate::color::Color;
-use crate::light::Light;
-use crate::ray::{Intersectable, Intersection, Ray};
-use crate::world::World;
+use crate::light::{Incident, Light};
+use crate::registry;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DirectionalLight {
     pub direction: Vec3,
     pub color: Color,
     pub intensity: f32,
 }
 
-impl Light for DirectionalLight {
-    fn shading(&self, world: &World, intersection: &Intersection) -> Color {
-        let light_direction = -self.direction;
-
-        let shadow_ray = Ray::shadow(intersection, light_direction);
+impl DirectionalLight {
+    /// A light shining along `direction`, which does not need to be normalized.
+    pub fn new(direction: Vec3, color: Color, intensity: f32) -> DirectionalLight {
+        DirectionalLight {
+            direction: direction.normalized(),
+            color,
+            intensity,
+        }
+    }
+}
 
-        let in_light = world.intersect(&shadow_ray).is_none();
-        let intensity = if in_light { self.intensity } else { 0.0 };
+impl Light for DirectionalLight {
+    fn illuminate(&self, _point: Vec3) -> Incident {
+        Incident {
+            direction: -self.direction,
+            distance: f32::INFINITY,
+            radiance: self.color * self.intensity,
+        }
+    }
 
-        let light_power = intersection.contact.normal.dot(light_direction) * intensity;
-        let light_reflected = intersection.contact.material.albedo / std::f32::consts::PI;
-        let color = intersection.color() * self.color * light_power * light_reflected;
-        color.clamp()
+    fn to_config(&self) -> Option<Value> {
+        registry::tagged("directional", self)
     }
 }
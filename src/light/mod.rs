@@ -1,10 +1,41 @@
+use serde_yaml::Value;
+use ultraviolet::Vec3;
+
 use crate::color::Color;
-use crate::ray::Intersection;
+use crate::ray::{Intersection, Ray};
 use crate::world::World;
 
 pub mod directional;
 pub mod point;
+pub mod spot;
+
+/// Unoccluded light arriving at a point.
+pub struct Incident {
+    /// Unit vector pointing from the point towards the light.
+    pub direction: Vec3,
+    /// Distance to the light along `direction`.
+    pub distance: f32,
+    pub radiance: Color,
+}
 
 pub trait Light {
-    fn shading(&self, world: &World, intersection: &Intersection) -> Color;
+    fn illuminate(&self, point: Vec3) -> Incident;
+
+    fn shading(&self, world: &World, intersection: &Intersection) -> Color {
+        let incident = self.illuminate(intersection.hit);
+
+        let shadow_ray = Ray::shadow(intersection, incident.direction);
+        let visibility = world.visibility(&shadow_ray, 0.0..incident.distance);
+
+        let cos_theta = intersection.contact.normal.dot(incident.direction).max(0.0);
+        let light_power = cos_theta * visibility;
+        let light_reflected = intersection.contact.material.albedo / std::f32::consts::PI;
+        intersection.color() * incident.radiance * light_power * light_reflected
+    }
+
+    /// The config entry that loads this light, used when saving scenes. Lights that cannot
+    /// be written to a config return `None`.
+    fn to_config(&self) -> Option<Value> {
+        None
+    }
 }
@@ -1,38 +1,101 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use ultraviolet::Vec3;
 use crate::color::Color;
-use crate::light::Light;
-use crate::ray::{Intersectable, Intersection, Ray};
-use crate::world::World;
+use crate::light::{Incident, Light};
+use crate::registry;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PointLight {
-    position: Vec3,
-    color: Color,
-    intensity: f32,
+    pub position: Vec3,
+    pub color: Color,
+    pub intensity: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Color, intensity: f32) -> PointLight {
+        PointLight {
+            position,
+            color,
+            intensity,
+        }
+    }
 }
 
 impl Light for PointLight {
-    fn shading(&self, world: &World, intersection: &Intersection) -> Color {
-        let light_direction = (self.position - intersection.hit).normalized();
+    fn illuminate(&self, point: Vec3) -> Incident {
+        let to_light = self.position - point;
+        let distance = to_light.mag();
+        let intensity = self.intensity / (4.0 * std::f32::consts::PI * distance * distance);
 
-        let distance = (self.position - intersection.hit).mag_sq();
-        let intensity = self.intensity / (4.0 * std::f32::consts::PI * distance);
+        Incident {
+            direction: to_light / distance,
+            distance,
+            radiance: self.color * intensity,
+        }
+    }
 
-        let in_light = {
-            let shadow_ray = Ray::shadow(intersection, light_direction);
-            let shadow_intersect = world.intersect(&shadow_ray);
+    fn to_config(&self) -> Option<Value> {
+        registry::tagged("point", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
 
-            match shadow_intersect {
-                Some(shadow_intersection) => shadow_intersection.hit.mag_sq() > (self.position - intersection.hit).mag_sq(),
-                None => true,
-            }
-        };
+    use crate::color::Color;
+    use crate::light::Light;
+    use crate::model::plane::Plane;
+    use crate::model::sphere::Sphere;
+    use crate::model::Material;
+    use crate::ray::{Intersectable, Ray};
+    use crate::world::World;
 
-        let intensity = if in_light { intensity } else { 0.0 };
-        let light_power = intersection.contact.normal.dot(light_direction) * intensity;
-        let light_reflected = intersection.contact.material.albedo / std::f32::consts::PI;
-        let color = intersection.color() * self.color * light_power * light_reflected;
-        color.clamp()
+    use super::PointLight;
+
+    fn world_with_occluder(occluder: Vec3) -> World {
+        let mut world = World::new();
+        world.entities.push(Box::new(Plane {
+            position: Vec3::new(0.0, 1.0, 0.0),
+            normal: Vec3::unit_y(),
+            material: Material {
+                albedo: 1.0,
+                ..Material::default()
+            },
+            ..Plane::default()
+        }));
+        world.entities.push(Box::new(Sphere {
+            center: occluder,
+            radius: 0.5,
+            ..Sphere::default()
+        }));
+        world
     }
-}
\ No newline at end of file
+
+    fn shade(world: &World, light: &PointLight) -> Color {
+        let ray = Ray::new(Vec3::new(5.0, -1.0, 5.0), Vec3::unit_y());
+        let intersection = world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        light.shading(world, &intersection)
+    }
+
+    fn light() -> PointLight {
+        PointLight {
+            position: Vec3::new(5.0, -2.0, 5.0),
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 100.0,
+        }
+    }
+
+    #[test]
+    fn occluder_beyond_light_casts_no_shadow() {
+        let world = world_with_occluder(Vec3::new(5.0, -6.0, 5.0));
+        assert_ne!(shade(&world, &light()), Color::default());
+    }
+
+    #[test]
+    fn occluder_between_surface_and_light_casts_shadow() {
+        let world = world_with_occluder(Vec3::new(5.0, -0.5, 5.0));
+        assert_eq!(shade(&world, &light()), Color::default());
+    }
+}
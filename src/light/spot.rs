@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use ultraviolet::Vec3;
+
+use crate::color::Color;
+use crate::light::{Incident, Light};
+use crate::registry;
+
+/// A point light that only shines within a cone, fading out between its inner and outer
+/// angle.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Color,
+    /// Power of a point light as bright as the center of the cone.
+    pub intensity: f32,
+    /// Angle from the direction in degrees at which the light starts to fade.
+    #[serde(default)]
+    pub inner_angle: f32,
+    /// Angle from the direction in degrees beyond which there is no light.
+    #[serde(default = "SpotLight::default_outer_angle")]
+    pub outer_angle: f32,
+}
+
+impl SpotLight {
+    /// A light shining along `direction`, which does not need to be normalized, in a 45° cone.
+    pub fn new(position: Vec3, direction: Vec3, color: Color, intensity: f32) -> SpotLight {
+        SpotLight {
+            position,
+            direction: direction.normalized(),
+            color,
+            intensity,
+            inner_angle: 0.0,
+            outer_angle: SpotLight::default_outer_angle(),
+        }
+    }
+
+    /// Sets the angles in degrees at which the light starts and finishes fading out.
+    pub fn with_cone(self, inner_angle: f32, outer_angle: f32) -> SpotLight {
+        SpotLight {
+            inner_angle,
+            outer_angle,
+            ..self
+        }
+    }
+
+    /// How much of the light reaches `direction`, pointing away from the light.
+    fn falloff(&self, direction: Vec3) -> f32 {
+        let cos_outer = self.outer_angle.to_radians().cos();
+        let cos_inner = self.inner_angle.min(self.outer_angle).to_radians().cos();
+        let cos_theta = direction.dot(self.direction);
+
+        if cos_inner - cos_outer <= f32::EPSILON {
+            return if cos_theta >= cos_outer { 1.0 } else { 0.0 };
+        }
+
+        let t = ((cos_theta - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    fn default_outer_angle() -> f32 {
+        45.0
+    }
+}
+
+impl Light for SpotLight {
+    fn illuminate(&self, point: Vec3) -> Incident {
+        let to_light = self.position - point;
+        let distance = to_light.mag();
+        let direction = to_light / distance;
+
+        let intensity = self.intensity / (4.0 * std::f32::consts::PI * distance * distance);
+
+        Incident {
+            direction,
+            distance,
+            radiance: self.color * intensity * self.falloff(-direction),
+        }
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        registry::tagged("spot", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::color::Color;
+    use crate::light::Light;
+
+    use super::SpotLight;
+
+    #[test]
+    fn light_fades_between_the_cone_angles() {
+        let light = SpotLight::new(Vec3::zero(), Vec3::unit_y(), Color::new(1.0, 1.0, 1.0), 1.0)
+            .with_cone(20.0, 40.0);
+        let brightness = |degrees: f32| {
+            let angle = degrees.to_radians();
+            let point = Vec3::new(angle.sin(), angle.cos(), 0.0);
+            <[f32; 3]>::from(light.illuminate(point).radiance)[0]
+        };
+
+        let center = brightness(0.0);
+        assert!(center > 0.0);
+        assert!((brightness(15.0) - center).abs() < 1e-6);
+        assert!(brightness(30.0) > 0.0 && brightness(30.0) < center);
+        assert_eq!(brightness(45.0), 0.0);
+        assert_eq!(brightness(180.0), 0.0);
+    }
+}
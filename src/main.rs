@@ -1,64 +1,157 @@
-use std::path::PathBuf;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
-use clap::Parser;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Parser, Subcommand};
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
-use crate::ray::Screen;
-use crate::scene::{Renderer, Scene};
+use raytracer::aov::Aov;
+use raytracer::format::Format;
+use raytracer::import;
+use raytracer::registry::Registry;
+use raytracer::serialize::{self, WorldConfig};
+use raytracer::validate::Diagnostic;
+use raytracer::{output, render_buffer, RenderSettings, Scene};
 
-mod camera;
-mod color;
-mod light;
-mod model;
-mod ray;
-mod scene;
-mod serialize;
-mod texture;
-mod world;
+use crate::viewer::Renderer;
+use crate::watch::Watcher;
+
+mod bench;
+mod viewer;
+mod watch;
 
 #[derive(Debug, Parser)]
 #[command(version, about)]
 struct Args {
-    #[clap(short, long, default_value = "assets/config.yaml")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[clap(short, long, global = true, default_value = "assets/config.yaml")]
     config: PathBuf,
+    /// Format of the config, instead of the one its extension names.
+    #[clap(long, global = true, value_parser = choice(&Format::ALL, Format::extension))]
+    format: Option<Format>,
+    /// Render a single frame to this file instead of opening a window.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Extra passes to write next to the beauty image.
+    #[clap(long, value_delimiter = ',', value_parser = choice(&Aov::ALL, Aov::name))]
+    aov: Vec<Aov>,
+    /// Samples per pixel for headless renders.
+    #[clap(short, long, global = true, default_value_t = 1)]
+    samples: u32,
+    /// Render every frame of the config's animation. A run of `#` in the output path is
+    /// replaced by the frame number, which is otherwise appended to the file name.
+    #[clap(long, requires = "output")]
+    animate: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Report problems in the config without rendering, exiting with an error if there are any.
+    Check,
+    /// Render the scene headlessly several times, reporting how long it took and how many
+    /// rays were traced.
+    Bench {
+        /// Number of timed renders, after one untimed render to warm up.
+        #[clap(long, default_value_t = 5)]
+        runs: u32,
+        /// Print the report as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+/// Parses one of `values` by its name, listing the names in the help.
+fn choice<T>(values: &'static [T], name: fn(T) -> &'static str) -> impl TypedValueParser<Value = T>
+where
+    T: Copy + Send + Sync + 'static,
+{
+    let names = values.iter().map(move |&value| name(value));
+    PossibleValuesParser::new(names).map(move |chosen| {
+        *values
+            .iter()
+            .find(|&&value| name(value) == chosen)
+            .expect("clap only accepts the listed names")
+    })
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let config = std::fs::read_to_string(&args.config).context("read config file")?;
-    let (world, camera) = serialize::parse_config(&config)?;
+    let format = args
+        .format
+        .unwrap_or_else(|| Format::from_path(&args.config).unwrap_or_default());
+
+    match args.command {
+        Some(Command::Check) => return check(&args.config, format),
+        Some(Command::Bench { runs, json }) => {
+            let (scene, _) = load(&args.config, format)?;
+            let report = bench::run(&args.config, &scene, args.samples, runs);
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{report}");
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if let Some(output) = &args.output {
+        if args.animate {
+            return render_sequence(&args.config, format, output, &args.aov, args.samples);
+        }
 
-    let mut scene = Scene::new(camera, world);
+        let (scene, _) = load(&args.config, format)?;
+        return render_to_file(&scene, output, &args.aov, args.samples);
+    }
+
+    let (mut scene, files) = load(&args.config, format)?;
+    let mut watcher = Watcher::new(files, Duration::from_millis(500));
+    let mut camera_moved = false;
 
     let mut input = WinitInputHelper::new();
     let event_loop = EventLoop::new();
 
     let window = WindowBuilder::new()
         .with_title("Raytracing in Rust")
-        .with_inner_size(scene.window_size())
+        .with_inner_size(viewer::window_size(&scene))
         .build(&event_loop)?;
 
-    let mut renderer = Renderer::new(&scene, &window)?;
-
     let fps = 60.0;
 
-    let frame_time = std::time::Duration::from_secs_f64(1.0 / fps);
+    let frame_time = Duration::from_secs_f64(1.0 / fps);
     let mut last_draw = Instant::now();
+    let mut moving = false;
+
+    let mut renderer = Renderer::new(&scene, &window, frame_time)?;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
         if let Event::RedrawRequested(_) = event {
-            if let Err(e) = renderer.render(&scene) {
+            let result = if moving {
+                renderer.render_preview(&scene)
+            } else {
+                renderer.render(&scene)
+            };
+
+            if let Err(e) = result {
                 eprintln!("Error rendering frame: {:?}", e);
                 *control_flow = ControlFlow::ExitWithCode(1);
                 return;
             }
+
+            let status = if moving {
+                format!("1/{} resolution", renderer.preview_scale())
+            } else {
+                format!("{} samples", renderer.samples())
+            };
+            window.set_title(&format!("Raytracing in Rust ({status})"));
         }
 
         if input.update(&event) {
@@ -70,19 +163,235 @@ fn main() -> anyhow::Result<()> {
                 return;
             }
 
+            if input.key_pressed(VirtualKeyCode::N) {
+                scene.denoiser.enabled = !scene.denoiser.enabled;
+                println!(
+                    "denoiser {}",
+                    if scene.denoiser.enabled { "on" } else { "off" }
+                );
+                window.request_redraw();
+            }
+
+            if input.key_pressed(VirtualKeyCode::P) {
+                match serialize::save_camera(&args.config, format, &scene.camera) {
+                    Ok(path) => println!("saved camera to {}", path.display()),
+                    Err(e) => eprintln!("Error saving camera: {:?}", e),
+                }
+            }
+
             if let Some(size) = input.window_resized() {
                 renderer.resize(size);
-                scene.camera.resize(size);
+                scene.camera.resize(size.width, size.height);
+            }
+
+            if watcher.changed() {
+                match load(&args.config, format) {
+                    Ok((mut reloaded, files)) => {
+                        if camera_moved {
+                            reloaded.camera.origin = scene.camera.origin;
+                            reloaded.camera.yaw = scene.camera.yaw;
+                            reloaded.camera.pitch = scene.camera.pitch;
+                        }
+
+                        let size = window.inner_size();
+                        reloaded.camera.resize(size.width, size.height);
+                        scene = reloaded;
+                        watcher.watch(files);
+                        renderer.reset();
+                        window.request_redraw();
+                    }
+                    Err(e) => eprintln!("Error reloading {}: {:?}", args.config.display(), e),
+                }
             }
 
             let now = Instant::now();
             let delta = now.duration_since(last_draw);
 
             if delta > frame_time {
-                scene.update(&input);
+                moving = viewer::steer(&mut scene.camera, &input);
+                camera_moved |= moving;
+
+                if moving {
+                    renderer.reset();
+                }
+
                 window.request_redraw();
                 last_draw = now;
             }
         }
     })
 }
+
+fn check(path: &Path, format: Format) -> anyhow::Result<()> {
+    if let Some(import) = import::open(path) {
+        let warnings = import?.warnings;
+        for warning in &warnings {
+            println!("{warning}");
+        }
+
+        println!("{}: 0 errors, {} warnings", path.display(), warnings.len());
+        return Ok(());
+    }
+
+    let (config, diagnostics) = WorldConfig::check(path, format, &Registry::default())?;
+    let mut warnings = diagnostics.iter().filter(|d| !d.is_error()).count();
+    let mut errors = diagnostics.len() - warnings;
+
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    // Some problems, such as unreadable volume grids, only show up once the scene is built.
+    if let Some(config) = config {
+        match serialize::build_config(&config) {
+            Ok((_, skipped)) => {
+                for warning in &skipped {
+                    println!("{warning}");
+                }
+                warnings += skipped.len();
+            }
+            Err(e) => {
+                println!("error: {e:#}");
+                errors += 1;
+            }
+        }
+    }
+
+    println!("{}: {errors} errors, {warnings} warnings", path.display());
+
+    if errors > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Loads the scene in `path` along with every file it was built from, importing it if it was
+/// written for another renderer.
+fn load(path: &Path, format: Format) -> anyhow::Result<(Scene, Vec<PathBuf>)> {
+    if let Some(import) = import::open(path) {
+        let import = import?;
+        warn(&import.warnings);
+
+        return Ok((import.scene, import.files));
+    }
+
+    let (config, warnings) = WorldConfig::open(path, format, &Registry::default())?;
+    warn(&warnings);
+    let (scene, warnings) = serialize::build_config(&config)?;
+    warn(&warnings);
+
+    eprintln!(
+        "loaded world with {} lights, {} entities, {} volumes",
+        scene.world.lights.len(),
+        scene.world.entities.len(),
+        scene.world.volumes.len(),
+    );
+
+    let mut files = vec![path.to_path_buf()];
+    files.extend(config.dependencies());
+
+    Ok((scene, files))
+}
+
+fn warn(warnings: &[Diagnostic]) {
+    for warning in warnings {
+        eprintln!("{warning}");
+    }
+}
+
+fn render_to_file(scene: &Scene, path: &Path, aovs: &[Aov], samples: u32) -> anyhow::Result<()> {
+    let settings = RenderSettings {
+        samples,
+        aovs: !aovs.is_empty(),
+        ..RenderSettings::default()
+    };
+
+    let start = Instant::now();
+    let buffer = render_buffer(scene, &settings);
+    println!(
+        "rendered {} samples in {:.2?}",
+        buffer.samples(),
+        start.elapsed()
+    );
+
+    output::save(path, &buffer, &scene.tone_mapping, aovs)?;
+    println!("saved {}", path.display());
+
+    Ok(())
+}
+
+fn render_sequence(
+    config: &Path,
+    format: Format,
+    path: &Path,
+    aovs: &[Aov],
+    samples: u32,
+) -> anyhow::Result<()> {
+    let (config, warnings) = WorldConfig::open(config, format, &Registry::default())?;
+    warn(&warnings);
+    let animation = config
+        .animation()
+        .context("the config has no animation section")?;
+
+    println!(
+        "rendering frames {}..={} ({:.2}s at {} fps)",
+        animation.start,
+        animation.end,
+        (animation.end - animation.start + 1) as f32 / animation.fps,
+        animation.fps,
+    );
+
+    for frame in animation.frames() {
+        let (scene, warnings) = config.build(frame as f32)?;
+        warn(&warnings);
+        render_to_file(&scene, &frame_path(path, frame), aovs, samples)?;
+    }
+
+    Ok(())
+}
+
+/// Numbers `path` for one frame of a sequence, e.g. `render_####.png` becomes
+/// `render_0012.png` and `render.png` becomes `render_0012.png`.
+fn frame_path(path: &Path, frame: u32) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let name = match name.find('#') {
+        Some(start) => {
+            let width = name[start..].chars().take_while(|&c| c == '#').count();
+            let end = start + width;
+            format!("{}{frame:0width$}{}", &name[..start], &name[end..])
+        }
+        None => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            match path.extension() {
+                Some(extension) => format!("{stem}_{frame:04}.{}", extension.to_string_lossy()),
+                None => format!("{stem}_{frame:04}"),
+            }
+        }
+    };
+
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::frame_path;
+
+    #[test]
+    fn frame_paths() {
+        assert_eq!(
+            frame_path(Path::new("out/turntable_###.png"), 7),
+            PathBuf::from("out/turntable_007.png")
+        );
+        assert_eq!(
+            frame_path(Path::new("out/turntable.exr"), 12),
+            PathBuf::from("out/turntable_0012.exr")
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::ops::Range;
+
+use serde_yaml::Value;
+use ultraviolet::Vec3;
+
+use crate::model::mesh::Mesh;
+use crate::model::{Material, Texture, TextureCoord};
+use crate::ray::{Intersectable, Intersection, Ray};
+use crate::world::Entity;
+
+/// Meshes with their own materials that are placed and moved as one entity, such as the
+/// parts of a model.
+#[derive(Debug, Clone)]
+pub struct Group {
+    meshes: Vec<Mesh>,
+    /// The config entry the group was loaded from, which saving the scene writes back.
+    pub source: Option<Value>,
+}
+
+impl Group {
+    /// # Panics
+    /// If `meshes` is empty.
+    pub fn new(meshes: Vec<Mesh>) -> Group {
+        assert!(!meshes.is_empty(), "a group needs at least one mesh");
+        Group {
+            meshes,
+            source: None,
+        }
+    }
+
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    pub fn meshes_mut(&mut self) -> &mut [Mesh] {
+        &mut self.meshes
+    }
+
+    /// Sets the displacement of every mesh between shutter open and close.
+    pub fn set_motion(&mut self, motion: Vec3) {
+        for mesh in &mut self.meshes {
+            mesh.motion = motion;
+        }
+    }
+
+    /// The mesh whose surface `point` is on.
+    fn nearest(&self, point: Vec3) -> &Mesh {
+        self.meshes
+            .iter()
+            .map(|mesh| (mesh.distance(point), mesh))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, mesh)| mesh)
+            .unwrap()
+    }
+}
+
+impl Intersectable for Group {
+    fn intersect(&self, ray: &Ray, range: Range<f32>) -> Option<Intersection<'_>> {
+        let mut closest: Option<Intersection> = None;
+
+        for mesh in &self.meshes {
+            let end = closest.as_ref().map_or(range.end, |hit| hit.t);
+            if let Some(hit) = mesh.intersect(ray, range.start..end) {
+                closest = Some(hit);
+            }
+        }
+
+        closest
+    }
+}
+
+impl Texture for Group {
+    fn texture_coord(&self, contact: Vec3) -> TextureCoord {
+        self.nearest(contact).texture_coord(contact)
+    }
+}
+
+impl Entity for Group {
+    /// The material of the first mesh. Intersections carry the material of the mesh they hit.
+    fn material(&self) -> &Material {
+        &self.meshes[0].material
+    }
+
+    fn surface_normal(&self, contact: Vec3) -> Vec3 {
+        self.nearest(contact).surface_normal(contact)
+    }
+
+    fn offset(&self, time: f32) -> Vec3 {
+        self.meshes[0].offset(time)
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        self.source.clone()
+    }
+}
@@ -0,0 +1,428 @@
+use std::ops::Range;
+
+use anyhow::ensure;
+use serde_yaml::Value;
+use ultraviolet::{Mat4, Vec2, Vec3};
+
+use crate::bvh::{Aabb, Bvh};
+use crate::color::Color;
+use crate::model::{Material, Texture, TextureCoord};
+use crate::ray::{Contact, Intersectable, Intersection, Ray};
+use crate::world::Entity;
+
+/// Vertices and triangles of a mesh, as read from a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    /// One normal per vertex, or none to shade every triangle flat.
+    pub normals: Vec<Vec3>,
+    /// One texture coordinate per vertex, or none to use the barycentric coordinates.
+    pub uvs: Vec<Vec2>,
+    /// One linear color per vertex, or none to use the material's color.
+    pub colors: Vec<Color>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl MeshData {
+    /// Moves every vertex by `matrix`, keeping the normals perpendicular to the surface.
+    pub fn transform(&mut self, matrix: Mat4) {
+        let normal_matrix = matrix.inversed().transposed();
+
+        for position in &mut self.positions {
+            *position = matrix.transform_point3(*position);
+        }
+
+        for normal in &mut self.normals {
+            *normal = normal_matrix.transform_vec3(*normal).normalized();
+        }
+    }
+
+    /// Checks that the triangles only refer to existing vertices and that every vertex has
+    /// the same attributes.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let vertices = self.positions.len();
+
+        ensure!(
+            self.normals.is_empty() || self.normals.len() == vertices,
+            "{} normals given for {vertices} vertices",
+            self.normals.len()
+        );
+        ensure!(
+            self.uvs.is_empty() || self.uvs.len() == vertices,
+            "{} texture coordinates given for {vertices} vertices",
+            self.uvs.len()
+        );
+        ensure!(
+            self.colors.is_empty() || self.colors.len() == vertices,
+            "{} colors given for {vertices} vertices",
+            self.colors.len()
+        );
+
+        if let Some(index) = self
+            .triangles
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= vertices)
+        {
+            anyhow::bail!("vertex {index} is out of range for {vertices} vertices");
+        }
+
+        Ok(())
+    }
+}
+
+/// A triangle mesh, shaded smoothly when it has normals.
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    data: MeshData,
+    pub material: Material,
+    /// Displacement of the mesh between shutter open and close.
+    pub motion: Vec3,
+    /// The config entry the mesh was loaded from, which saving the scene writes back.
+    pub source: Option<Value>,
+    bvh: Bvh,
+}
+
+/// Where a ray hit a triangle.
+struct Hit {
+    triangle: usize,
+    /// Barycentric coordinates of the second and third vertex.
+    u: f32,
+    v: f32,
+}
+
+impl Mesh {
+    /// Fails if `data` does not pass [`MeshData::validate`].
+    pub fn new(data: MeshData, material: Material) -> anyhow::Result<Mesh> {
+        data.validate()?;
+
+        let bounds: Vec<_> = (0..data.triangles.len())
+            .map(|triangle| Aabb::of(vertices(&data, triangle)))
+            .collect();
+
+        Ok(Mesh {
+            bvh: Bvh::new(&bounds),
+            data,
+            material,
+            motion: Vec3::zero(),
+            source: None,
+        })
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    /// Möller–Trumbore intersection of `ray` with one triangle.
+    fn hit(&self, triangle: usize, ray: &Ray, range: Range<f32>) -> Option<(f32, Hit)> {
+        let [a, b, c] = vertices(&self.data, triangle);
+        let ab = b - a;
+        let ac = c - a;
+
+        let p = ray.direction.cross(ac);
+        let determinant = ab.dot(p);
+        if determinant.abs() < 1e-9 {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let to_origin = ray.origin - a;
+        let u = to_origin.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = to_origin.cross(ab);
+        let v = ray.direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = ac.dot(q) * inverse;
+        range.contains(&t).then_some((t, Hit { triangle, u, v }))
+    }
+
+    /// How far `point` is from the surface.
+    pub(crate) fn distance(&self, point: Vec3) -> f32 {
+        self.locate(point)
+            .map_or(f32::INFINITY, |(distance, _)| distance)
+    }
+
+    /// Finds the triangle nearest to `point` through the BVH, along with the barycentric
+    /// coordinates of the nearest point on it and its distance. Only callers of
+    /// [`Entity::surface_normal`] and [`Texture::texture_coord`] need this, since
+    /// intersections fill in their contact from the triangle they hit.
+    fn locate(&self, point: Vec3) -> Option<(f32, Hit)> {
+        self.bvh.nearest(point, |triangle| {
+            let [a, b, c] = vertices(&self.data, triangle);
+            let (u, v) = closest(point, a, b, c);
+            let nearest = a + (b - a) * u + (c - a) * v;
+            let distance = (point - nearest).mag();
+            (distance, (distance, Hit { triangle, u, v }))
+        })
+    }
+
+    fn normal(&self, hit: &Hit) -> Vec3 {
+        let [a, b, c] = self.data.triangles[hit.triangle].map(|i| i as usize);
+
+        if self.data.normals.is_empty() {
+            let [a, b, c] = [a, b, c].map(|i| self.data.positions[i]);
+            return (b - a).cross(c - a).normalized();
+        }
+
+        let normals = &self.data.normals;
+        let w = 1.0 - hit.u - hit.v;
+        (normals[a] * w + normals[b] * hit.u + normals[c] * hit.v).normalized()
+    }
+
+    fn uv(&self, hit: &Hit) -> TextureCoord {
+        if self.data.uvs.is_empty() {
+            return TextureCoord::new(hit.u, hit.v);
+        }
+
+        let [a, b, c] = self.data.triangles[hit.triangle].map(|i| self.data.uvs[i as usize]);
+        a * (1.0 - hit.u - hit.v) + b * hit.u + c * hit.v
+    }
+
+    fn color(&self, hit: &Hit) -> Option<Color> {
+        if self.data.colors.is_empty() {
+            return None;
+        }
+
+        let [a, b, c] = self.data.triangles[hit.triangle].map(|i| self.data.colors[i as usize]);
+        Some(a * (1.0 - hit.u - hit.v) + b * hit.u + c * hit.v)
+    }
+}
+
+impl Intersectable for Mesh {
+    fn intersect(&self, ray: &Ray, range: Range<f32>) -> Option<Intersection<'_>> {
+        let local = Ray {
+            origin: ray.origin - self.offset(ray.time),
+            ..ray.clone()
+        };
+
+        let (t, hit) = self.bvh.closest(&local, range, |triangle, range| {
+            let (t, hit) = self.hit(triangle, &local, range)?;
+            Some((t, (t, hit)))
+        })?;
+
+        // Triangles have no inside, so they are lit from whichever side the ray came from.
+        let mut normal = self.normal(&hit);
+        if normal.dot(ray.direction) > 0.0 {
+            normal = -normal;
+        }
+
+        Some(Intersection {
+            t,
+            hit: ray.at(t),
+            source: ray.clone(),
+            contact: Contact {
+                normal,
+                texture: self.uv(&hit),
+                material: &self.material,
+                color: self.color(&hit),
+            },
+            entity: 0,
+        })
+    }
+}
+
+impl Texture for Mesh {
+    fn texture_coord(&self, contact: Vec3) -> TextureCoord {
+        self.locate(contact)
+            .map_or(TextureCoord::zero(), |(_, hit)| self.uv(&hit))
+    }
+}
+
+impl Entity for Mesh {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn surface_normal(&self, contact: Vec3) -> Vec3 {
+        self.locate(contact)
+            .map_or(Vec3::zero(), |(_, hit)| self.normal(&hit))
+    }
+
+    fn offset(&self, time: f32) -> Vec3 {
+        self.motion * time
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        self.source.clone()
+    }
+}
+
+fn vertices(data: &MeshData, triangle: usize) -> [Vec3; 3] {
+    data.triangles[triangle].map(|i| data.positions[i as usize])
+}
+
+/// Barycentric coordinates of the second and third vertex for `point` projected onto the
+/// triangle `abc`.
+fn barycentric(point: Vec3, a: Vec3, b: Vec3, c: Vec3) -> (f32, f32) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+
+    let d00 = ab.dot(ab);
+    let d01 = ab.dot(ac);
+    let d11 = ac.dot(ac);
+    let d20 = ap.dot(ab);
+    let d21 = ap.dot(ac);
+    let denom = d00 * d11 - d01 * d01;
+
+    if denom.abs() < 1e-12 {
+        return (0.0, 0.0);
+    }
+
+    let u = (d11 * d20 - d01 * d21) / denom;
+    let v = (d00 * d21 - d01 * d20) / denom;
+    (u, v)
+}
+
+/// Barycentric coordinates of the second and third vertex for the point of the triangle
+/// `abc` nearest to `point`, which lies on an edge when the projection falls outside.
+fn closest(point: Vec3, a: Vec3, b: Vec3, c: Vec3) -> (f32, f32) {
+    let (u, v) = barycentric(point, a, b, c);
+    if u >= 0.0 && v >= 0.0 && u + v <= 1.0 {
+        return (u, v);
+    }
+
+    // How far along the edge from `from` to `to` the point nearest to `point` is.
+    let along = |from: Vec3, to: Vec3| {
+        let edge = to - from;
+        let t = (point - from).dot(edge) / edge.mag_sq().max(1e-12);
+        let t = t.clamp(0.0, 1.0);
+        ((from + edge * t - point).mag_sq(), t)
+    };
+
+    let (ab, t_ab) = along(a, b);
+    let (bc, t_bc) = along(b, c);
+    let (ca, t_ca) = along(c, a);
+
+    if ab <= bc && ab <= ca {
+        (t_ab, 0.0)
+    } else if bc <= ca {
+        (1.0 - t_bc, t_bc)
+    } else {
+        (0.0, 1.0 - t_ca)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::{Vec2, Vec3};
+
+    use crate::model::{Material, Texture};
+    use crate::ray::{Intersectable, Ray};
+    use crate::sampler;
+    use crate::world::Entity;
+
+    use super::{Mesh, MeshData};
+
+    /// A `size` by `size` grid of quads in the plane `z = depth`, two triangles each.
+    fn grid(size: u32, depth: f32) -> MeshData {
+        let mut data = MeshData::default();
+
+        for y in 0..=size {
+            for x in 0..=size {
+                data.positions.push(Vec3::new(x as f32, y as f32, depth));
+                data.uvs.push(Vec2::new(x as f32, y as f32) / size as f32);
+            }
+        }
+
+        for y in 0..size {
+            for x in 0..size {
+                let corner = y * (size + 1) + x;
+                let above = corner + size + 1;
+                data.triangles.push([corner, corner + 1, above]);
+                data.triangles.push([corner + 1, above + 1, above]);
+            }
+        }
+
+        data
+    }
+
+    #[test]
+    fn rays_hit_the_nearest_triangle() {
+        let mut data = grid(16, -10.0);
+        let back = grid(16, -20.0);
+        let offset = data.positions.len() as u32;
+        data.positions.extend(back.positions);
+        data.uvs.extend(back.uvs);
+        data.triangles
+            .extend(back.triangles.iter().map(|t| t.map(|i| i + offset)));
+
+        let mesh = Mesh::new(data, Material::default()).unwrap();
+        sampler::seed(7);
+
+        for _ in 0..200 {
+            let target = Vec3::new(
+                sampler::next_f32() * 16.0,
+                sampler::next_f32() * 16.0,
+                -10.0,
+            );
+            let ray = Ray::new(
+                Vec3::new(8.0, 8.0, 0.0),
+                (target - Vec3::new(8.0, 8.0, 0.0)).normalized(),
+            );
+            let hit = mesh.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+            assert!((hit.hit - target).mag() < 1e-3);
+            assert!((hit.contact.texture - target.xy() / 16.0).mag() < 1e-3);
+            assert!((hit.contact.normal - Vec3::unit_z()).mag() < 1e-5);
+        }
+
+        let past = Ray::new(
+            Vec3::new(8.0, 8.0, 0.0),
+            Vec3::new(0.0, 1.0, -0.1).normalized(),
+        );
+        assert!(mesh.intersect(&past, Ray::UNBOUNDED).is_none());
+
+        let behind = Ray::new(Vec3::new(4.0, 4.0, -15.0), -Vec3::unit_z());
+        let hit = mesh.intersect(&behind, Ray::UNBOUNDED).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn surface_queries_find_the_nearest_triangle() {
+        let mesh = Mesh::new(grid(16, -10.0), Material::default()).unwrap();
+        sampler::seed(11);
+
+        for _ in 0..200 {
+            let x = sampler::next_f32() * 16.0;
+            let y = sampler::next_f32() * 16.0;
+            let point = Vec3::new(x, y, -10.0 + 0.01 * (sampler::next_f32() - 0.5));
+
+            assert!((mesh.texture_coord(point) - Vec2::new(x, y) / 16.0).mag() < 1e-4);
+            assert!((mesh.surface_normal(point) - Vec3::unit_z()).mag() < 1e-5);
+        }
+
+        // Points beside the mesh take the nearest point on its edge.
+        let beside = mesh.texture_coord(Vec3::new(-3.0, 8.0, -10.0));
+        assert!((beside - Vec2::new(0.0, 0.5)).mag() < 1e-5);
+    }
+
+    #[test]
+    fn moving_meshes_are_shaded_where_they_are_hit() {
+        let mut mesh = Mesh::new(grid(4, -10.0), Material::default()).unwrap();
+        mesh.motion = Vec3::new(2.0, 0.0, 0.0);
+
+        let ray = Ray::new(Vec3::new(3.0, 1.0, 0.0), -Vec3::unit_z()).with_time(0.5);
+        let hit = mesh.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+        assert!((hit.t - 10.0).abs() < 1e-4);
+        assert!((hit.contact.texture - Vec2::new(2.0, 1.0) / 4.0).mag() < 1e-5);
+    }
+
+    #[test]
+    fn invalid_meshes_are_reported() {
+        let mut data = grid(1, 0.0);
+        data.triangles.push([0, 1, 9]);
+        assert!(data.validate().is_err());
+
+        let mut data = grid(1, 0.0);
+        data.normals.push(Vec3::unit_z());
+        assert!(data.validate().is_err());
+    }
+}
@@ -1,8 +1,12 @@
+use std::collections::HashMap;
+
 use crate::color::Color;
-use crate::texture::Coloration;
-use serde::Deserialize;
+use crate::texture::{Coloration, ColorationKey};
+use serde::{Deserialize, Serialize};
 use ultraviolet::{Vec2, Vec3};
 
+pub mod group;
+pub mod mesh;
 pub mod plane;
 pub mod sphere;
 
@@ -10,17 +14,39 @@ pub trait Texture {
     fn texture_coord(&self, contact: Vec3) -> TextureCoord;
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Material {
     #[serde(default)]
     pub color: Coloration,
     #[serde(default = "Material::default_albedo")]
     pub albedo: f32,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "SurfaceType::is_diffuse")]
     pub surface: SurfaceType,
+    /// Identifier written to the material id AOV, assigned when the scene is loaded.
+    #[serde(skip)]
+    pub id: usize,
 }
 
 impl Material {
+    /// A diffuse material.
+    pub fn new(color: impl Into<Coloration>, albedo: f32) -> Material {
+        Material {
+            color: color.into(),
+            albedo,
+            ..Material::default()
+        }
+    }
+
+    pub fn reflective(self, reflectivity: f32, roughness: f32) -> Material {
+        Material {
+            surface: SurfaceType::Reflective {
+                reflectivity,
+                roughness,
+            },
+            ..self
+        }
+    }
+
     pub fn color(&self, coord: TextureCoord) -> Color {
         self.color.get(&coord)
     }
@@ -30,22 +56,90 @@ impl Material {
     }
 }
 
+/// Hands out the ids written to the material id AOV. Materials with the same definition share
+/// an id, while blocks of ids can be reserved for materials told apart some other way, such as
+/// by their index in a glTF file.
+#[derive(Debug, Default)]
+pub struct MaterialIds {
+    known: HashMap<MaterialKey, usize>,
+    next: usize,
+}
+
+/// The fields that tell materials apart, with numbers compared by their bits.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct MaterialKey {
+    color: ColorationKey,
+    albedo: u32,
+    surface: Option<[u32; 2]>,
+}
+
+impl MaterialIds {
+    pub fn assign(&mut self, material: &mut Material) {
+        // Materials that cannot be compared, such as ones with textures decoded from memory,
+        // get an id of their own.
+        let Some(color) = material.color.key() else {
+            material.id = self.reserve(1);
+            return;
+        };
+
+        let key = MaterialKey {
+            color,
+            albedo: material.albedo.to_bits(),
+            surface: match material.surface {
+                SurfaceType::Diffuse => None,
+                SurfaceType::Reflective {
+                    reflectivity,
+                    roughness,
+                } => Some([reflectivity.to_bits(), roughness.to_bits()]),
+            },
+        };
+
+        material.id = match self.known.get(&key) {
+            Some(&id) => id,
+            None => {
+                let id = self.reserve(1);
+                self.known.insert(key, id);
+                id
+            }
+        };
+    }
+
+    /// Reserves `count` consecutive ids, returning the first.
+    pub fn reserve(&mut self, count: usize) -> usize {
+        let first = self.next;
+        self.next += count;
+        first
+    }
+}
+
 impl Default for Material {
     fn default() -> Self {
         Self {
             color: Coloration::Color(Color::new(1.0, 0.0, 0.0)),
             albedo: 0.0,
             surface: SurfaceType::Diffuse,
+            id: 0,
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(untagged)]
 pub enum SurfaceType {
     #[default]
     Diffuse,
-    Reflective { reflectivity: f32 }
+    Reflective {
+        reflectivity: f32,
+        /// Spread of the reflection lobe; `0.0` is a perfect mirror.
+        #[serde(default)]
+        roughness: f32,
+    },
+}
+
+impl SurfaceType {
+    pub fn is_diffuse(&self) -> bool {
+        matches!(self, SurfaceType::Diffuse)
+    }
 }
 
 pub type TextureCoord = Vec2;
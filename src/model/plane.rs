@@ -1,17 +1,24 @@
 use crate::model::{Material, Texture, TextureCoord};
 use crate::ray::{Intersectable, Intersection, Ray};
+use crate::registry;
 use crate::world::Entity;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::ops::Range;
 use ultraviolet::Vec3;
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct Plane {
     pub position: Vec3,
     pub normal: Vec3,
     pub material: Material,
+    /// Displacement of the plane between shutter open and close.
+    #[serde(rename = "velocity")]
+    pub motion: Vec3,
 }
 
 impl Intersectable for Plane {
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+    fn intersect(&self, ray: &Ray, range: Range<f32>) -> Option<Intersection<'_>> {
         let normal = self.normal;
         let denom = normal.dot(ray.direction);
 
@@ -19,17 +26,16 @@ impl Intersectable for Plane {
             return None;
         }
 
-        let relative = self.position - ray.origin;
+        let relative = self.position + self.offset(ray.time) - ray.origin;
         let distance = relative.dot(normal) / denom;
 
-        if distance < 0.0 {
+        if !range.contains(&distance) {
             return None;
         }
 
-        let hit_point = ray.origin + ray.direction * distance;
         let source = ray.clone();
 
-        let intersection = Intersection::new(hit_point, source, self);
+        let intersection = Intersection::new(distance, source, self);
 
         Some(intersection)
     }
@@ -61,4 +67,53 @@ impl Entity for Plane {
     fn surface_normal(&self, _contact: Vec3) -> Vec3 {
         -self.normal
     }
+
+    fn offset(&self, time: f32) -> Vec3 {
+        self.motion * time
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        registry::tagged("plane", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::ray::{Intersectable, Ray};
+
+    use super::Plane;
+
+    fn floor() -> Plane {
+        Plane {
+            position: Vec3::new(0.0, 1.0, 0.0),
+            normal: Vec3::unit_y(),
+            ..Plane::default()
+        }
+    }
+
+    #[test]
+    fn rays_along_the_normal_hit_the_plane() {
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 1.0, -1.0).normalized());
+        let plane = floor();
+        let intersection = plane.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+        assert!((intersection.t - 2.0_f32.sqrt()).abs() < 1e-5);
+        assert!((intersection.hit - Vec3::new(0.0, 1.0, -1.0)).mag() < 1e-5);
+        assert_eq!(intersection.contact.normal, -Vec3::unit_y());
+    }
+
+    #[test]
+    fn parallel_receding_and_distant_rays_miss() {
+        let plane = floor();
+        let parallel = Ray::new(Vec3::zero(), -Vec3::unit_z());
+        let receding = Ray::new(Vec3::zero(), -Vec3::unit_y());
+        let toward = Ray::new(Vec3::zero(), Vec3::unit_y());
+
+        assert!(plane.intersect(&parallel, Ray::UNBOUNDED).is_none());
+        assert!(plane.intersect(&receding, Ray::UNBOUNDED).is_none());
+        assert!(plane.intersect(&toward, 0.0..0.5).is_none());
+        assert!(plane.intersect(&toward, 0.0..1.5).is_some());
+    }
 }
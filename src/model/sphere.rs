@@ -1,19 +1,28 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use serde_yaml::Value;
 use ultraviolet::Vec3;
 
 use crate::model::{Material, Texture, TextureCoord};
 use crate::ray::{Intersectable, Intersection, Ray};
+use crate::registry;
 use crate::world::Entity;
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct Sphere {
+    #[serde(rename = "position")]
     pub center: Vec3,
     pub radius: f32,
     pub material: Material,
+    /// Displacement of the center between shutter open and close.
+    #[serde(rename = "velocity")]
+    pub motion: Vec3,
 }
 
 impl Intersectable for Sphere {
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        let local = self.center - ray.origin;
+    fn intersect(&self, ray: &Ray, range: Range<f32>) -> Option<Intersection<'_>> {
+        let local = self.center + self.offset(ray.time) - ray.origin;
         let projected = local.dot(ray.direction);
         let distance = local.mag_sq() - projected * projected;
         let radius_sq = self.radius * self.radius;
@@ -26,22 +35,12 @@ impl Intersectable for Sphere {
         let edge_front = projected - thickness;
         let edge_back = projected + thickness;
 
-        if edge_front < 0.0 && edge_back < 0.0 {
-            return None;
-        }
-
-        let distance_to_edge = if edge_front < 0.0 {
-            edge_back
-        } else if edge_back < 0.0 {
-            edge_front
-        } else {
-            edge_front.min(edge_back)
-        };
+        let t = [edge_front, edge_back]
+            .into_iter()
+            .find(|t| range.contains(t))?;
 
-        let hit_point = ray.origin + ray.direction * distance_to_edge;
         let source = ray.clone();
-
-        let intersection = Intersection::new(hit_point, source, self);
+        let intersection = Intersection::new(t, source, self);
 
         Some(intersection)
     }
@@ -68,4 +67,66 @@ impl Entity for Sphere {
     fn surface_normal(&self, contact: Vec3) -> Vec3 {
         (contact - self.center).normalized()
     }
+
+    fn offset(&self, time: f32) -> Vec3 {
+        self.motion * time
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        registry::tagged("sphere", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::ray::{Intersectable, Ray};
+    use crate::world::Entity;
+
+    use super::Sphere;
+
+    fn sphere() -> Sphere {
+        Sphere {
+            center: Vec3::new(0.0, 0.0, -5.0),
+            radius: 1.0,
+            ..Sphere::default()
+        }
+    }
+
+    #[test]
+    fn rays_hit_the_near_side_first() {
+        let sphere = sphere();
+        let ray = Ray::new(Vec3::zero(), -Vec3::unit_z());
+        let intersection = sphere.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+        assert!((intersection.t - 4.0).abs() < 1e-5);
+        assert!((intersection.hit - Vec3::new(0.0, 0.0, -4.0)).mag() < 1e-5);
+        assert!((intersection.contact.normal - Vec3::unit_z()).mag() < 1e-5);
+    }
+
+    #[test]
+    fn rays_from_inside_hit_the_far_side() {
+        let sphere = sphere();
+        let ray = Ray::new(sphere.center, Vec3::unit_x());
+        let intersection = sphere.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+        assert!((intersection.t - 1.0).abs() < 1e-5);
+        assert!((sphere.surface_normal(intersection.hit) - Vec3::unit_x()).mag() < 1e-5);
+    }
+
+    #[test]
+    fn misses_and_hits_outside_the_range_are_ignored() {
+        let sphere = sphere();
+        let aside = Ray::new(Vec3::new(0.0, 1.5, 0.0), -Vec3::unit_z());
+        let behind = Ray::new(Vec3::zero(), Vec3::unit_z());
+        let ahead = Ray::new(Vec3::zero(), -Vec3::unit_z());
+
+        assert!(sphere.intersect(&aside, Ray::UNBOUNDED).is_none());
+        assert!(sphere.intersect(&behind, Ray::UNBOUNDED).is_none());
+        assert!(sphere.intersect(&ahead, 0.0..3.0).is_none());
+
+        let far = sphere.intersect(&ahead, 4.5..10.0).unwrap();
+        assert!((far.t - 6.0).abs() < 1e-5);
+    }
 }
@@ -0,0 +1,264 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, ImageAttributes, IntegerBounds, Layer,
+    LayerAttributes, WritableImage,
+};
+use image::codecs::hdr::HdrEncoder;
+use image::Rgb;
+
+use crate::aov::Aov;
+use crate::film::{Framebuffer, ToneMapping};
+
+/// Writes a rendered frame to `path`, picking the format from its extension.
+///
+/// Float formats store linear radiance without tone mapping. OpenEXR files hold every
+/// requested AOV as an extra layer, while `.hdr` and `.pfm` write each AOV to a sibling
+/// file named after it. Other formats are tone mapped to 8-bit and cannot hold AOVs.
+pub fn save(
+    path: &Path,
+    buffer: &Framebuffer,
+    tone_mapping: &ToneMapping,
+    aovs: &[Aov],
+) -> anyhow::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("exr") => save_exr(path, buffer, aovs),
+
+        Some(format @ ("hdr" | "pfm")) => {
+            let save_rgb = |path: &Path, data: Vec<f32>| match format {
+                "hdr" => save_hdr(path, buffer, data),
+                _ => save_pfm(path, buffer, &data, 3),
+            };
+
+            save_rgb(path, layer(buffer, None)?)?;
+
+            for &aov in aovs {
+                let path = sibling(path, aov);
+                let data = layer(buffer, Some(aov))?;
+
+                match aov.channels().len() {
+                    3 => save_rgb(&path, data)?,
+                    1 if format == "pfm" => save_pfm(&path, buffer, &data, 1)?,
+                    channels => save_rgb(&path, pad_to_rgb(&data, channels))?,
+                }
+            }
+
+            Ok(())
+        }
+
+        _ => {
+            if !aovs.is_empty() {
+                bail!(
+                    "{} cannot store AOVs, use .exr, .hdr or .pfm",
+                    path.display()
+                );
+            }
+
+            buffer
+                .to_image(tone_mapping)
+                .save(path)
+                .with_context(|| format!("write {}", path.display()))
+        }
+    }
+}
+
+fn save_exr(path: &Path, buffer: &Framebuffer, aovs: &[Aov]) -> anyhow::Result<()> {
+    let size = (buffer.width() as usize, buffer.height() as usize);
+
+    let mut layers = vec![exr_layer(
+        size,
+        "beauty",
+        &["R", "G", "B"],
+        layer(buffer, None)?,
+    )];
+
+    for &aov in aovs {
+        let data = layer(buffer, Some(aov))?;
+        layers.push(exr_layer(size, aov.name(), aov.channels(), data));
+    }
+
+    let attributes = ImageAttributes::new(IntegerBounds::from_dimensions(size));
+    Image::from_layers(attributes, layers)
+        .write()
+        .to_file(path)
+        .with_context(|| format!("write {}", path.display()))
+}
+
+fn exr_layer(
+    size: (usize, usize),
+    name: &str,
+    channels: &[&str],
+    data: Vec<f32>,
+) -> Layer<AnyChannels<FlatSamples>> {
+    let count = channels.len();
+
+    let channels = channels
+        .iter()
+        .enumerate()
+        .map(|(offset, &channel)| {
+            let samples = data.iter().skip(offset).step_by(count).copied().collect();
+            AnyChannel::new(channel, FlatSamples::F32(samples))
+        })
+        .collect();
+
+    Layer::new(
+        size,
+        LayerAttributes::named(name),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels),
+    )
+}
+
+/// Radiance RGBE.
+fn save_hdr(path: &Path, buffer: &Framebuffer, data: Vec<f32>) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+    let pixels: Vec<_> = data
+        .chunks_exact(3)
+        .map(|rgb| Rgb([rgb[0], rgb[1], rgb[2]]))
+        .collect();
+
+    HdrEncoder::new(BufWriter::new(file))
+        .encode(&pixels, buffer.width() as usize, buffer.height() as usize)
+        .with_context(|| format!("write {}", path.display()))
+}
+
+/// Portable float map: a tiny text header followed by little-endian rows, bottom to top.
+fn save_pfm(
+    path: &Path,
+    buffer: &Framebuffer,
+    data: &[f32],
+    channels: usize,
+) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    let magic = if channels == 1 { "Pf" } else { "PF" };
+    write!(
+        writer,
+        "{magic}\n{} {}\n-1.0\n",
+        buffer.width(),
+        buffer.height()
+    )?;
+
+    let row = buffer.width() as usize * channels;
+    for line in data.chunks_exact(row).rev() {
+        for value in line {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Interleaved values of either the beauty pass or one of the AOVs.
+fn layer(buffer: &Framebuffer, aov: Option<Aov>) -> anyhow::Result<Vec<f32>> {
+    let Some(aov) = aov else {
+        return Ok(buffer
+            .pixels()
+            .iter()
+            .flat_map(|&color| <[f32; 3]>::from(color))
+            .collect());
+    };
+
+    let samples = buffer.aovs().context("frame was rendered without AOVs")?;
+    Ok(samples
+        .iter()
+        .flat_map(|sample| sample.values(aov))
+        .collect())
+}
+
+/// Single channel AOVs are repeated as grey, two channel ones get an empty blue channel.
+fn pad_to_rgb(data: &[f32], channels: usize) -> Vec<f32> {
+    data.chunks_exact(channels)
+        .flat_map(|pixel| match *pixel {
+            [value] => [value; 3],
+            [x, y] => [x, y, 0.0],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        })
+        .collect()
+}
+
+/// `render.pfm` with the depth AOV becomes `render.depth.pfm`.
+fn sibling(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{}.{extension}", aov.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use exr::prelude::read_all_data_from_file;
+
+    use crate::aov::{Aov, AovSample};
+    use crate::color::Color;
+    use crate::film::{Framebuffer, ToneMapping};
+
+    use super::save;
+
+    fn frame() -> Framebuffer {
+        let mut buffer = Framebuffer::with_aovs(2, 2);
+        let sample = AovSample {
+            depth: 4.0,
+            ..AovSample::default()
+        };
+        buffer.accumulate(3, Color::new(8.0, 0.5, 0.25), sample);
+        buffer
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytracer-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn exr_contains_a_layer_per_aov() {
+        let path = temp_path("layers.exr");
+        save(
+            &path,
+            &frame(),
+            &ToneMapping::default(),
+            &[Aov::Depth, Aov::Uv],
+        )
+        .unwrap();
+
+        let image = read_all_data_from_file(&path).unwrap();
+        let names: Vec<_> = image
+            .layer_data
+            .iter()
+            .map(|layer| layer.attributes.layer_name.as_ref().unwrap().to_string())
+            .collect();
+
+        assert_eq!(names, ["beauty", "depth", "uv"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pfm_keeps_unclamped_values_bottom_up() {
+        let path = temp_path("beauty.pfm");
+        save(&path, &frame(), &ToneMapping::default(), &[Aov::Depth]).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let header = b"PF\n2 2\n-1.0\n";
+        assert!(bytes.starts_with(header));
+
+        let first = &bytes[header.len() + 12..header.len() + 16];
+        assert_eq!(f32::from_le_bytes(first.try_into().unwrap()), 8.0);
+
+        let depth_path =
+            path.with_file_name(format!("raytracer-{}-beauty.depth.pfm", std::process::id()));
+        assert!(std::fs::read(&depth_path).unwrap().starts_with(b"Pf\n"));
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(depth_path).unwrap();
+    }
+}
@@ -6,34 +6,52 @@ use ultraviolet::{Vec2, Vec3};
 use crate::camera::Camera;
 use crate::color::Color;
 use crate::model::{Material, TextureCoord};
+use crate::sampler;
 use crate::world::Entity;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// When the ray was cast, from `0.0` at shutter open to `1.0` at shutter close.
+    pub time: f32,
 }
 
 impl Ray {
     const BIAS: f32 = 1e-3;
 
+    /// Accepts every intersection in front of the ray origin.
+    pub const UNBOUNDED: Range<f32> = 0.0..f32::INFINITY;
+
     pub fn new(origin: Vec3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(self, time: f32) -> Ray {
+        Ray { time, ..self }
     }
 
-    pub fn from_screen(camera: &Camera, pos: Vec2) -> Ray {
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    pub fn from_screen(camera: &Camera, pos: Vec2, time: f32) -> Ray {
         let x = pos.x * camera.aspect_ratio() * camera.fov;
         let y = pos.y * camera.fov;
 
-        let origin = camera.origin;
-        let direction = Vec3::new(x, y, -1.0).normalized();
+        let origin = camera.origin_at(time);
+        let direction = (camera.orientation() * Vec3::new(x, y, -1.0)).normalized();
 
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_time(time)
     }
 
     pub fn shadow(intersection: &Intersection, direction: Vec3) -> Ray {
         let origin = intersection.hit + direction * Ray::BIAS;
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_time(intersection.source.time)
     }
 
     pub fn reflect(intersection: &Intersection) -> Ray {
@@ -41,7 +59,20 @@ impl Ray {
         let incident = intersection.source.direction;
         let origin = intersection.hit + normal * Ray::BIAS;
         let direction = incident - (2.0 * incident.dot(normal) * normal);
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_time(intersection.source.time)
+    }
+
+    /// Reflects the incident ray around a lobe whose width is controlled by `roughness`,
+    /// falling back to the mirror direction if the perturbed ray would go below the surface.
+    pub fn glossy(intersection: &Intersection, roughness: f32) -> Ray {
+        let mut ray = Ray::reflect(intersection);
+        let direction = (ray.direction + sampler::in_unit_sphere() * roughness).normalized();
+
+        if direction.dot(intersection.contact.normal) > 0.0 {
+            ray.direction = direction;
+        }
+
+        ray
     }
 }
 
@@ -61,37 +92,42 @@ fn clamp(value: f32, from: Range<f32>, to: Range<f32>) -> f32 {
 }
 
 pub trait Intersectable {
-    fn intersect(&self, ray: &Ray) -> Option<Intersection>;
+    /// Finds the nearest intersection whose ray parameter `t` lies within `range`.
+    fn intersect(&self, ray: &Ray, range: Range<f32>) -> Option<Intersection<'_>>;
 }
 
 pub struct Intersection<'a> {
+    /// Distance along `source` to the hit point, in units of its direction.
+    pub t: f32,
     pub hit: Vec3,
     pub source: Ray,
     pub contact: Contact<'a>,
+    /// Index of the hit entity in `World::entities`, filled in by the world.
+    pub entity: usize,
 }
 
 impl<'a> Intersection<'a> {
-    pub fn new(hit: Vec3, source: Ray, entity: &impl Entity) -> Intersection<'_> {
-        let contact = Contact::new(hit, entity);
+    pub fn new(t: f32, source: Ray, entity: &impl Entity) -> Intersection<'_> {
+        let hit = source.at(t);
+        // Shade a moving entity where the hit lies on it at rest, so textures travel with it.
+        let contact = Contact::new(hit - entity.offset(source.time), entity);
         Intersection {
+            t,
             hit,
             source,
             contact,
+            entity: 0,
         }
     }
 
-    pub fn magnitude_sq(&self) -> f32 {
-        self.hit.mag_sq()
-    }
-
     pub fn color(&self) -> Color {
-        self.contact.material.color(self.contact.texture)
+        self.contact
+            .color
+            .unwrap_or_else(|| self.contact.material.color(self.contact.texture))
     }
 
     pub fn closest(a: &Intersection, b: &Intersection) -> Ordering {
-        a.magnitude_sq()
-            .partial_cmp(&b.magnitude_sq())
-            .unwrap_or(Ordering::Equal)
+        a.t.partial_cmp(&b.t).unwrap_or(Ordering::Equal)
     }
 }
 
@@ -99,6 +135,9 @@ pub struct Contact<'a> {
     pub normal: Vec3,
     pub texture: TextureCoord,
     pub material: &'a Material,
+    /// Color painted on the surface itself, such as the vertex colors of a scanned mesh,
+    /// which takes the place of the material's color.
+    pub color: Option<Color>,
 }
 
 impl<'a> Contact<'a> {
@@ -107,6 +146,47 @@ impl<'a> Contact<'a> {
             normal: entity.surface_normal(point),
             texture: entity.texture_coord(point),
             material: entity.material(),
+            color: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::{Vec2, Vec3};
+
+    use crate::camera::Camera;
+    use crate::model::plane::Plane;
+
+    use super::{Intersectable, Ray, Screen};
+
+    #[test]
+    fn glossy_reflection_stays_above_surface() {
+        let plane = Plane {
+            position: Vec3::new(0.0, 1.0, 0.0),
+            normal: Vec3::unit_y(),
+            ..Plane::default()
+        };
+
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 1.0, -1.0).normalized());
+        let intersection = plane.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        let mirror = Ray::reflect(&intersection);
+
+        for _ in 0..64 {
+            let glossy = Ray::glossy(&intersection, 1.0);
+            assert!(glossy.direction.dot(intersection.contact.normal) > 0.0);
+            assert!((glossy.direction.mag() - 1.0).abs() < 1e-4);
+            assert!(glossy.direction.dot(mirror.direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn screen_maps_pixels_to_unit_square() {
+        let camera = Camera::new(200, 100);
+
+        assert_eq!(camera.at(0.0, 0.0), Vec2::new(-1.0, -1.0));
+        assert_eq!(camera.at(100.0, 50.0), Vec2::new(0.0, 0.0));
+        assert_eq!(camera.at(200.0, 100.0), Vec2::new(1.0, 1.0));
+        assert_eq!(camera.at(50.0, 75.0), Vec2::new(-0.5, 0.5));
+    }
+}
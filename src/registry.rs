@@ -0,0 +1,443 @@
+//! Entity, light and texture types added by users of the library. Once registered under a
+//! name in a [`Registry`], a type is loaded from any config entry whose `type` is that name,
+//! just like the built-in ones, by configs opened with that registry:
+//!
+//! ```yaml
+//! entity:
+//!   - type: torus
+//!     position: [0, 0, -5]
+//!     radii: [1, 0.25]
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use anyhow::ensure;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+
+use crate::color::Color;
+use crate::light::Light;
+use crate::model::TextureCoord;
+use crate::texture::ProceduralTexture;
+use crate::world::Entity;
+
+const ENTITIES: &[&str] = &["sphere", "plane", "mesh", "gltf"];
+const LIGHTS: &[&str] = &["directional", "point", "spot"];
+
+type Factory<T> = Arc<dyn Fn(Value) -> anyhow::Result<T> + Send + Sync>;
+type Factories<T> = HashMap<String, Factory<T>>;
+
+/// The custom types a config can use next to the built-in ones. The default registry has
+/// none, and clones share the functions that build each type.
+#[derive(Clone, Default)]
+pub struct Registry {
+    entities: Factories<Box<dyn Entity>>,
+    lights: Factories<Box<dyn Light>>,
+    textures: Factories<Arc<dyn ProceduralTexture>>,
+}
+
+thread_local! {
+    /// The registry that config entries are being deserialized with on this thread.
+    static CURRENT: RefCell<Option<Registry>> = const { RefCell::new(None) };
+}
+
+impl Registry {
+    /// Loads entities of type `kind` by deserializing them into `E`. Fails if `kind` is a
+    /// built-in entity type or already registered.
+    pub fn register_entity<E>(&mut self, kind: &str) -> anyhow::Result<()>
+    where
+        E: Entity + DeserializeOwned + 'static,
+    {
+        self.register_entity_with(kind, |value| Ok(Box::new(deserialize::<E>(value)?)))
+    }
+
+    /// Loads entities of type `kind` with `build`, which is given the entry without its `type`.
+    pub fn register_entity_with(
+        &mut self,
+        kind: &str,
+        build: impl Fn(Value) -> anyhow::Result<Box<dyn Entity>> + Send + Sync + 'static,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            !ENTITIES.contains(&kind),
+            "`{kind}` is a built-in entity type"
+        );
+        insert(&mut self.entities, "entity", kind, Arc::new(build))
+    }
+
+    /// Loads lights of type `kind` by deserializing them into `L`. Fails if `kind` is a
+    /// built-in light type or already registered.
+    pub fn register_light<L>(&mut self, kind: &str) -> anyhow::Result<()>
+    where
+        L: Light + DeserializeOwned + 'static,
+    {
+        self.register_light_with(kind, |value| Ok(Box::new(deserialize::<L>(value)?)))
+    }
+
+    /// Loads lights of type `kind` with `build`, which is given the entry without its `type`.
+    pub fn register_light_with(
+        &mut self,
+        kind: &str,
+        build: impl Fn(Value) -> anyhow::Result<Box<dyn Light>> + Send + Sync + 'static,
+    ) -> anyhow::Result<()> {
+        ensure!(!LIGHTS.contains(&kind), "`{kind}` is a built-in light type");
+        insert(&mut self.lights, "light", kind, Arc::new(build))
+    }
+
+    /// Loads material colors of type `kind` by deserializing them into `T`. Plain colors and
+    /// image textures have no `type`, so this only fails if `kind` is already registered.
+    pub fn register_texture<T>(&mut self, kind: &str) -> anyhow::Result<()>
+    where
+        T: ProceduralTexture + DeserializeOwned + 'static,
+    {
+        self.register_texture_with(kind, |value| Ok(Arc::new(deserialize::<T>(value)?)))
+    }
+
+    /// Loads material colors of type `kind` with `build`, which is given the entry without
+    /// its `type`.
+    pub fn register_texture_with(
+        &mut self,
+        kind: &str,
+        build: impl Fn(Value) -> anyhow::Result<Arc<dyn ProceduralTexture>> + Send + Sync + 'static,
+    ) -> anyhow::Result<()> {
+        insert(&mut self.textures, "texture", kind, Arc::new(build))
+    }
+
+    /// Removes every type registered as `kind`, returning whether there was one.
+    pub fn unregister(&mut self, kind: &str) -> bool {
+        let entity = self.entities.remove(kind).is_some();
+        let light = self.lights.remove(kind).is_some();
+        let texture = self.textures.remove(kind).is_some();
+        entity || light || texture
+    }
+
+    /// Every entity type a config can use, built-in ones first.
+    pub fn entity_types(&self) -> Vec<String> {
+        types(ENTITIES, &self.entities)
+    }
+
+    /// Every light type a config can use, built-in ones first.
+    pub fn light_types(&self) -> Vec<String> {
+        types(LIGHTS, &self.lights)
+    }
+
+    pub fn is_texture(&self, kind: &str) -> bool {
+        self.textures.contains_key(kind)
+    }
+
+    /// Runs `f` with entries deserialized on this thread loading the types of this registry.
+    pub(crate) fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Registry>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let _restore = Restore(previous);
+        f()
+    }
+}
+
+impl Debug for Registry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("entities", &types(&[], &self.entities))
+            .field("lights", &types(&[], &self.lights))
+            .field("textures", &types(&[], &self.textures))
+            .finish()
+    }
+}
+
+fn insert<T>(
+    factories: &mut Factories<T>,
+    category: &str,
+    kind: &str,
+    factory: Factory<T>,
+) -> anyhow::Result<()> {
+    ensure!(
+        !factories.contains_key(kind),
+        "the {category} type `{kind}` is already registered"
+    );
+    factories.insert(kind.into(), factory);
+    Ok(())
+}
+
+/// Calls `f` with the registry entries are being deserialized with, or with one that only
+/// knows the built-in types outside of [`Registry::scope`].
+fn with_current<R>(f: impl FnOnce(&Registry) -> R) -> R {
+    CURRENT.with(|current| match &*current.borrow() {
+        Some(registry) => f(registry),
+        None => f(&Registry::default()),
+    })
+}
+
+/// Deserializes a config entry the same way as the built-in types, which also accept
+/// vectors written as `[x, y, z]`.
+pub fn deserialize<T: DeserializeOwned>(value: Value) -> Result<T, serde_yaml::Error> {
+    // Flattening buffers the fields the way tagged enums do, which is what lets structs be
+    // read from sequences.
+    #[derive(Deserialize)]
+    struct Entry<T> {
+        #[serde(flatten)]
+        fields: T,
+    }
+
+    serde_yaml::from_value::<Entry<T>>(value).map(|entry| entry.fields)
+}
+
+/// Serializes `entry` as a config entry of type `kind`, for implementing `to_config`.
+pub fn tagged(kind: &str, entry: &impl Serialize) -> Option<Value> {
+    let Value::Mapping(fields) = serde_yaml::to_value(entry).ok()? else {
+        return None;
+    };
+
+    let mut tagged = Mapping::new();
+    tagged.insert("type".into(), kind.into());
+    tagged.extend(fields);
+    Some(Value::Mapping(tagged))
+}
+
+/// The `type` of a config entry.
+pub(crate) fn tag(value: &Value) -> Option<&str> {
+    value.get("type").and_then(Value::as_str)
+}
+
+/// Builds a custom texture, or returns `None` if `kind` was never registered.
+pub(crate) fn texture(
+    kind: &str,
+    value: &Value,
+) -> Option<anyhow::Result<Arc<dyn ProceduralTexture>>> {
+    let factory = with_current(|registry| registry.textures.get(kind).cloned())?;
+    let texture = factory(untagged(value));
+
+    Some(texture.map(|texture| {
+        Arc::new(Loaded {
+            texture,
+            source: value.clone(),
+        }) as Arc<dyn ProceduralTexture>
+    }))
+}
+
+/// A registered texture along with the entry it was loaded from, so that it can be saved
+/// whether or not it implements `to_config` itself.
+#[derive(Debug)]
+struct Loaded {
+    texture: Arc<dyn ProceduralTexture>,
+    source: Value,
+}
+
+impl ProceduralTexture for Loaded {
+    fn get(&self, uv: &TextureCoord) -> Color {
+        self.texture.get(uv)
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        Some(self.source.clone())
+    }
+}
+
+/// A config entry of a registered type, kept as written along with the registry it was
+/// loaded with so that it can be built again for every frame.
+#[derive(Debug, Clone)]
+pub(crate) struct Custom {
+    pub kind: String,
+    value: Value,
+    registry: Registry,
+}
+
+impl Serialize for Custom {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+impl Custom {
+    /// Picks out entries of registered entity types, checking that they can be built.
+    pub fn entity(value: &Value) -> Option<anyhow::Result<Custom>> {
+        let custom = Custom::registered(value, |registry| &registry.entities)?;
+        Some(custom.build_entity().map(|_| custom))
+    }
+
+    /// Picks out entries of registered light types, checking that they can be built.
+    pub fn light(value: &Value) -> Option<anyhow::Result<Custom>> {
+        let custom = Custom::registered(value, |registry| &registry.lights)?;
+        Some(custom.build_light().map(|_| custom))
+    }
+
+    pub fn build_entity(&self) -> anyhow::Result<Box<dyn Entity>> {
+        self.build(&self.registry.entities)
+    }
+
+    pub fn build_light(&self) -> anyhow::Result<Box<dyn Light>> {
+        self.build(&self.registry.lights)
+    }
+
+    fn registered<T>(value: &Value, factories: fn(&Registry) -> &Factories<T>) -> Option<Custom> {
+        let kind = tag(value)?;
+
+        with_current(|registry| {
+            factories(registry).contains_key(kind).then(|| Custom {
+                kind: kind.into(),
+                value: value.clone(),
+                registry: registry.clone(),
+            })
+        })
+    }
+
+    fn build<T>(&self, factories: &Factories<T>) -> anyhow::Result<T> {
+        let factory = &factories[&self.kind];
+        // The entry may hold registered textures of its own.
+        self.registry.scope(|| factory(untagged(&self.value)))
+    }
+}
+
+/// A copy of `value` without its `type`.
+fn untagged(value: &Value) -> Value {
+    let mut value = value.clone();
+    if let Value::Mapping(fields) = &mut value {
+        fields.remove("type");
+    }
+    value
+}
+
+fn types<T>(builtin: &[&str], factories: &Factories<T>) -> Vec<String> {
+    let mut custom: Vec<_> = factories.keys().cloned().collect();
+    custom.sort();
+
+    builtin
+        .iter()
+        .map(|kind| kind.to_string())
+        .chain(custom)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ultraviolet::{Vec2, Vec3};
+
+    use crate::color::Color;
+    use crate::document::Document;
+    use crate::format::Format;
+    use crate::light::point::PointLight;
+    use crate::model::sphere::Sphere;
+    use crate::model::{Material, TextureCoord};
+    use crate::serialize::WorldConfig;
+    use crate::texture::ProceduralTexture;
+    use crate::validate::validate;
+
+    use super::Registry;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Stripes {
+        width: f32,
+    }
+
+    impl ProceduralTexture for Stripes {
+        fn get(&self, uv: &TextureCoord) -> Color {
+            if (uv.x / self.width) as i32 % 2 == 0 {
+                Color::new(1.0, 1.0, 1.0)
+            } else {
+                Color::default()
+            }
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Ball {
+        position: Vec3,
+        material: Material,
+    }
+
+    const CONFIG: &str = "
+camera: { width: 4, height: 4 }
+light:
+  - type: lamp
+    position: [0, -2, 0]
+    color: 0xFFFFFF
+    intensity: 10
+entity:
+  - type: ball
+    position: [0, 0, -3]
+    material:
+      color: { type: stripes, width: 0.5 }
+";
+
+    fn registry() -> Registry {
+        let mut registry = Registry::default();
+        registry
+            .register_entity_with("ball", |value| {
+                let ball: Ball = super::deserialize(value)?;
+                Ok(Box::new(Sphere {
+                    center: ball.position,
+                    radius: 1.0,
+                    material: ball.material,
+                    ..Sphere::default()
+                }))
+            })
+            .unwrap();
+        registry.register_light::<PointLight>("lamp").unwrap();
+        registry.register_texture::<Stripes>("stripes").unwrap();
+        registry
+    }
+
+    #[test]
+    fn registered_types_load_from_configs() {
+        let registry = registry();
+        let document = Document::parse(CONFIG, Path::new("test.yaml"), Format::Yaml).unwrap();
+        assert_eq!(validate(&document, &registry), vec![]);
+
+        let config: WorldConfig = registry
+            .scope(|| serde_yaml::from_value(document.value))
+            .unwrap();
+        let (scene, _) = config.build(0.0).unwrap();
+        assert_eq!(scene.world.lights.len(), 1);
+        assert_eq!(scene.world.entities.len(), 1);
+
+        let color = &scene.world.entities[0].material().color;
+        assert_eq!(color.get(&Vec2::new(0.25, 0.0)), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(color.get(&Vec2::new(0.75, 0.0)), Color::default());
+    }
+
+    #[test]
+    fn unregistered_types_are_reported() {
+        let document = Document::parse(CONFIG, Path::new("test.yaml"), Format::Yaml).unwrap();
+        let mut registry = registry();
+        assert!(registry.unregister("ball"));
+        assert!(!registry.unregister("ball"));
+
+        // Types registered elsewhere are not seen either.
+        for registry in [registry, Registry::default()] {
+            let paths: Vec<_> = validate(&document, &registry)
+                .into_iter()
+                .map(|diagnostic| diagnostic.path)
+                .collect();
+
+            assert!(paths.contains(&"entity[0].type".to_string()));
+            let config =
+                registry.scope(|| serde_yaml::from_value::<WorldConfig>(document.value.clone()));
+            assert!(config.is_err());
+        }
+    }
+
+    #[test]
+    fn built_in_and_registered_types_cannot_be_replaced() {
+        let mut registry = registry();
+
+        let error = registry.register_light::<PointLight>("point").unwrap_err();
+        assert!(error.to_string().contains("built-in"));
+        assert!(registry.register_light::<PointLight>("lamp").is_err());
+        assert!(registry.register_texture::<Stripes>("stripes").is_err());
+        assert!(registry.register_texture::<Stripes>("dots").is_ok());
+    }
+}
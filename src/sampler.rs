@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use ultraviolet::Vec3;
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::seed_from_u64(0));
+}
+
+/// Restarts the current thread's sequence, so a pixel's samples don't depend on the
+/// order in which pixels are rendered.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+/// Returns a uniformly distributed number in `[0, 1)`.
+pub fn next_f32() -> f32 {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+/// Returns a uniformly distributed point inside the unit sphere.
+pub fn in_unit_sphere() -> Vec3 {
+    loop {
+        let point = Vec3::new(next_f32(), next_f32(), next_f32()) * 2.0 - Vec3::one();
+
+        if point.mag_sq() < 1.0 {
+            return point;
+        }
+    }
+}
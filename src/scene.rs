@@ -1,17 +1,18 @@
-use anyhow::Context;
-use pixels::{Error, Pixels, SurfaceTexture};
+use image::RgbaImage;
 use ultraviolet::Vec2;
-use winit::dpi::PhysicalSize;
-use winit::window::Window;
-use winit_input_helper::WinitInputHelper;
 
 use crate::camera::Camera;
-use crate::ray::{Intersectable, Ray, Screen};
+use crate::denoise::Denoiser;
+use crate::film::{Framebuffer, ToneMapping};
+use crate::ray::{Ray, Screen};
+use crate::sampler;
 use crate::world::World;
 
 pub struct Scene {
     pub camera: Camera,
     pub world: World,
+    pub tone_mapping: ToneMapping,
+    pub denoiser: Denoiser,
 }
 
 impl Scene {
@@ -22,26 +23,45 @@ impl Scene {
     pub fn height(&self) -> u32 {
         self.camera.viewport_height as u32
     }
-
-    pub fn window_size(&self) -> PhysicalSize<u32> {
-        PhysicalSize::new(self.width(), self.height())
-    }
-
-    pub fn update(&mut self, input: &WinitInputHelper) {
-        self.camera.update(input);
-    }
 }
 
 impl Scene {
     pub fn new(camera: Camera, world: World) -> Self {
-        Self { camera, world }
+        Self {
+            camera,
+            world,
+            tone_mapping: ToneMapping::default(),
+            denoiser: Denoiser::default(),
+        }
     }
 
-    fn pos(&self, index: usize) -> Vec2 {
-        let width = self.width();
+    /// Traces one more sample for every pixel of `buffer` and accumulates it as linear
+    /// radiance. Each sample is jittered within its pixel and in time across the shutter, and
+    /// a buffer smaller than the camera viewport covers the same view at a lower resolution.
+    pub fn render(&self, buffer: &mut Framebuffer) {
+        let sample = buffer.samples() as u64;
+        let scale = self.size() / Vec2::new(buffer.width() as f32, buffer.height() as f32);
+
+        for index in 0..buffer.pixels().len() {
+            sampler::seed(sample << 32 | index as u64);
+
+            let jitter = Vec2::new(sampler::next_f32(), sampler::next_f32());
+            let pixel = Self::pixel(index, buffer.width()) + jitter;
+            let pos = pixel * scale;
+
+            let time = sampler::next_f32();
+            let ray = Ray::from_screen(&self.camera, self.at(pos.x, pos.y), time);
+            let (color, aov) = self.world.cast_primary(ray);
+            buffer.accumulate(index, color, aov);
+        }
+
+        buffer.finish_sample();
+    }
+
+    fn pixel(index: usize, width: u32) -> Vec2 {
         let x = index as u32 % width;
         let y = index as u32 / width;
-        self.at(x as f32, y as f32)
+        Vec2::new(x as f32, y as f32)
     }
 }
 
@@ -51,47 +71,56 @@ impl Screen for Scene {
     }
 }
 
-pub struct Renderer {
-    pixels: Pixels,
+/// A tone mapped 8-bit image, as returned by [`render`].
+pub type Image = RgbaImage;
+
+/// Options for rendering a scene without a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    /// Samples per pixel.
+    pub samples: u32,
+    /// Output size in pixels, defaulting to the camera viewport. The view itself always
+    /// matches the camera.
+    pub resolution: Option<(u32, u32)>,
+    /// Keeps the AOV buffers in the result. They are always rendered when the scene's
+    /// denoiser is enabled, since it needs them.
+    pub aovs: bool,
 }
 
-impl Renderer {
-    pub fn new(scene: &Scene, window: &Window) -> anyhow::Result<Self> {
-        let surface_texture = SurfaceTexture::new(scene.width(), scene.height(), window);
-        let pixels = Pixels::new(scene.width(), scene.height(), surface_texture)
-            .context("create pixels renderer")?;
-        Ok(Self { pixels })
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            samples: 1,
+            resolution: None,
+            aovs: false,
+        }
     }
+}
 
-    pub fn render(&mut self, scene: &Scene) -> Result<(), Error> {
-        let frame = self.pixels.frame_mut();
-        Self::draw(scene, frame);
-        self.pixels.render()
-    }
+/// Renders `scene` and tone maps the result with the scene's settings.
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Image {
+    render_buffer(scene, settings).to_image(&scene.tone_mapping)
+}
 
-    fn draw(scene: &Scene, frame: &mut [u8]) {
-        for (index, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let ray = Ray::from_screen(&scene.camera, scene.pos(index));
+/// Renders `scene` into a linear framebuffer, denoised if the scene's denoiser is enabled.
+pub fn render_buffer(scene: &Scene, settings: &RenderSettings) -> Framebuffer {
+    let (width, height) = settings
+        .resolution
+        .unwrap_or((scene.width(), scene.height()));
 
-            let intersected_color = scene
-                .world
-                .intersect(&ray)
-                .map_or(scene.world.sky_color(&ray), |intersection| {
-                    scene.world.compute_light(intersection, 0)
-                });
+    let mut buffer = if settings.aovs || scene.denoiser.enabled {
+        Framebuffer::with_aovs(width, height)
+    } else {
+        Framebuffer::new(width, height)
+    };
 
-            pixel.copy_from_slice(&intersected_color.as_slice());
-        }
+    for _ in 0..settings.samples.max(1) {
+        scene.render(&mut buffer);
     }
 
-    pub fn resize(&mut self, size: impl Into<PhysicalSize<u32>>) {
-        let size = size.into();
-
-        self.pixels
-            .resize_surface(size.width, size.height)
-            .expect("resize window");
-        self.pixels
-            .resize_buffer(size.width, size.height)
-            .expect("resize buffer");
+    if scene.denoiser.enabled {
+        buffer = scene.denoiser.apply(&buffer);
     }
+
+    buffer
 }
@@ -1,78 +1,464 @@
-use anyhow::Context;
-use serde::Deserialize;
-use ultraviolet::Vec3;
+use std::f32::consts::TAU;
+use std::fs;
+use std::path::{Path, PathBuf};
 
+use anyhow::{bail, ensure, Context};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use ultraviolet::{Mat4, Vec3};
+
+use crate::animation::{Animation, CameraProperty, EntityProperty, LightProperty, Target};
 use crate::camera::Camera;
+use crate::color::Color;
+use crate::denoise::Denoiser;
+use crate::document::Document;
+use crate::film::ToneMapping;
+use crate::format::Format;
+use crate::import::{self, gltf};
 use crate::light::directional::DirectionalLight;
 use crate::light::point::PointLight;
+use crate::light::spot::SpotLight;
 use crate::light::Light;
+use crate::model::mesh::Mesh;
 use crate::model::plane::Plane;
 use crate::model::sphere::Sphere;
-use crate::model::Material;
-use crate::world::{Entity, Sky, World};
+use crate::model::{Material, MaterialIds, SurfaceType};
+use crate::registry::{Custom, Registry};
+use crate::scene::Scene;
+use crate::texture::Coloration;
+use crate::validate::{validate, Diagnostic};
+use crate::volume::grid::Grid;
+use crate::volume::heterogeneous::{Emission, HeterogeneousVolume};
+use crate::volume::homogeneous::HomogeneousVolume;
+use crate::volume::Volume;
+use crate::world::{Entity, Fog, Sky, World};
 
-pub fn parse_config(config: &str) -> anyhow::Result<(World, Camera)> {
-    let result: WorldConfig = serde_yaml::from_str(config).context("parsing config file")?;
+/// Loads the scene in `path`, in the format its extension names or YAML, with only the
+/// built-in types. Warnings are dropped; use [`WorldConfig::open`] and [`build_config`] to see
+/// them or to load custom types.
+pub fn parse_config(path: &Path) -> anyhow::Result<Scene> {
+    let format = Format::from_path(path).unwrap_or_default();
+    let (config, _) = WorldConfig::open(path, format, &Registry::default())?;
+    build_config(&config).map(|(scene, _)| scene)
+}
 
-    let mut world = World::new();
+/// Writes `scene` to `path` as a config that [`parse_config`] loads back, in the format its
+/// extension names or YAML. Scenes with custom types are saved with
+/// [`WorldConfig::from_scene`].
+pub fn save_config(scene: &Scene, path: &Path) -> anyhow::Result<()> {
+    let format = Format::from_path(path).unwrap_or_default();
+    WorldConfig::from_scene(scene, &Registry::default())?.save(path, format)
+}
 
-    world.reflection_depth = result.reflection_depth;
-    world.sky = result.sky;
+/// Writes a copy of the config in `path` with the camera moved to where `camera` is and
+/// facing where it faces, returning the path of the copy. The copy sits next to the original
+/// so that relative paths keep working, and only the camera differs from it.
+pub fn save_camera(path: &Path, format: Format, camera: &Camera) -> anyhow::Result<PathBuf> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut document = format.parse(&text)?;
 
-    for light in result.light {
-        world.lights.push(light.build());
-    }
+    let Value::Mapping(root) = &mut document else {
+        bail!("{} is not a mapping", path.display());
+    };
 
-    for entity in result.entity {
-        world.entities.push(entity.build());
-    }
+    let entry = root
+        .entry("camera".into())
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    let Value::Mapping(entry) = entry else {
+        bail!("the camera in {} is not a mapping", path.display());
+    };
 
-    let camera = result.camera.build();
+    let pose = CameraConfig::from(camera);
+    entry.insert("origin".into(), serde_yaml::to_value(pose.origin)?);
+    entry.insert("yaw".into(), pose.yaw.into());
+    entry.insert("pitch".into(), pose.pitch.into());
 
-    println!(
-        "loaded world with {} lights, {} entities",
-        world.lights.len(),
-        world.entities.len(),
-    );
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let copy = path.with_file_name(format!("{stem}.camera.{}", format.extension()));
+    fs::write(&copy, format.write(&document)?)
+        .with_context(|| format!("writing {}", copy.display()))?;
 
-    Ok((world, camera))
+    Ok(copy)
+}
+
+/// Builds the scene shown in the viewer, which is the first frame of any animation, along
+/// with any warnings from building it.
+pub fn build_config(config: &WorldConfig) -> anyhow::Result<(Scene, Vec<Diagnostic>)> {
+    let frame = config.animation().map_or(0, |animation| animation.start);
+    config.build(frame as f32)
 }
 
 fn reflection_depth() -> usize {
     5
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct WorldConfig {
+fn reflection_samples() -> usize {
+    8
+}
+
+/// The scene as written in the config file, before anything is built from it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorldConfig {
     camera: CameraConfig,
     light: Vec<LightConfig>,
     entity: Vec<EntityConfig>,
+    /// Rules that add more entities after the ones in `entity`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    generate: Vec<GeneratorConfig>,
     #[serde(default = "reflection_depth")]
     reflection_depth: usize,
+    #[serde(default = "reflection_samples")]
+    reflection_samples: usize,
     #[serde(default)]
     sky: Sky,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fog: Option<Fog>,
+    #[serde(default)]
+    volume: Vec<VolumeConfig>,
+    #[serde(default)]
+    tone_mapping: ToneMapping,
+    #[serde(default)]
+    denoise: Denoiser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    animation: Option<Animation>,
+    #[serde(default, skip_serializing_if = "Shutter::is_instant")]
+    shutter: Shutter,
+    /// Files merged into this one through `include`.
+    #[serde(skip)]
+    includes: Vec<PathBuf>,
+}
+
+impl WorldConfig {
+    /// Loads the config in `path` along with its warnings, failing with every error found.
+    /// Entries may use the custom types of `registry`.
+    pub fn open(
+        path: &Path,
+        format: Format,
+        registry: &Registry,
+    ) -> anyhow::Result<(WorldConfig, Vec<Diagnostic>)> {
+        let (config, diagnostics) = WorldConfig::check(path, format, registry)?;
+
+        match config {
+            Some(config) => Ok((config, diagnostics)),
+            None => {
+                let errors: Vec<_> = diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.is_error())
+                    .map(Diagnostic::to_string)
+                    .collect();
+                bail!("invalid config\n{}", errors.join("\n"))
+            }
+        }
+    }
+
+    /// Loads the config in `path` and reports what is wrong with it. The config is only
+    /// returned when there are no errors. Files that cannot be read or parsed as YAML at all
+    /// are reported as an `Err`.
+    pub fn check(
+        path: &Path,
+        format: Format,
+        registry: &Registry,
+    ) -> anyhow::Result<(Option<WorldConfig>, Vec<Diagnostic>)> {
+        let document = Document::open(path, format)?;
+        let mut diagnostics = validate(&document, registry);
+
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Ok((None, diagnostics));
+        }
+
+        let mut error = |path: &str, message: String| {
+            let mut diagnostic = Diagnostic::error(path, message);
+            diagnostic.location = document.locate(path).cloned();
+            diagnostics.push(diagnostic);
+        };
+
+        let config = registry.scope(|| serde_path_to_error::deserialize(document.value.clone()));
+        let mut config: WorldConfig = match config {
+            Ok(config) => config,
+            Err(e) => {
+                error(&e.path().to_string(), e.into_inner().to_string());
+                return Ok((None, diagnostics));
+            }
+        };
+        config.includes = document.includes.clone();
+
+        if config.shutter.open > config.shutter.close {
+            error("shutter", "the shutter closes before it opens".into());
+        }
+
+        if let Some(animation) = &config.animation {
+            // Catch tracks pointing at missing entities or properties before rendering.
+            if let Err(e) = config.at_frame(animation.start as f32) {
+                error("animation.tracks", format!("{e:#}"));
+            }
+        }
+
+        let valid = !diagnostics.iter().any(Diagnostic::is_error);
+        Ok((valid.then_some(config), diagnostics))
+    }
+
+    /// Describes a scene as a config. Entities that moved while the shutter was open are
+    /// given the velocity that repeats the motion over a one frame shutter. Fails if the
+    /// scene holds anything that cannot be written to a config, or custom types missing from
+    /// `registry`.
+    pub fn from_scene(scene: &Scene, registry: &Registry) -> anyhow::Result<WorldConfig> {
+        registry.scope(|| WorldConfig::describe(scene))
+    }
+
+    fn describe(scene: &Scene) -> anyhow::Result<WorldConfig> {
+        let world = &scene.world;
+
+        let light = world
+            .lights
+            .iter()
+            .enumerate()
+            .map(|(index, light)| load("light", index, light.to_config()))
+            .collect::<anyhow::Result<Vec<LightConfig>>>()?;
+
+        let entity = world
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| load("entity", index, entity.to_config()))
+            .collect::<anyhow::Result<Vec<EntityConfig>>>()?;
+
+        let volume = world
+            .volumes
+            .iter()
+            .enumerate()
+            .map(|(index, volume)| load("volume", index, volume.to_config()))
+            .collect::<anyhow::Result<_>>()?;
+
+        let moving = entity
+            .iter()
+            .any(|entity| entity.velocity() != Vec3::zero());
+        let shutter = Shutter {
+            open: 0.0,
+            close: if moving { 1.0 } else { 0.0 },
+        };
+
+        Ok(WorldConfig {
+            camera: CameraConfig::from(&scene.camera),
+            light,
+            entity,
+            generate: Vec::new(),
+            reflection_depth: world.reflection_depth,
+            reflection_samples: world.reflection_samples,
+            sky: world.sky.clone(),
+            fog: world.fog.clone(),
+            volume,
+            tone_mapping: scene.tone_mapping.clone(),
+            denoise: scene.denoiser.clone(),
+            animation: None,
+            shutter,
+            includes: Vec::new(),
+        })
+    }
+
+    /// Writes the config to `path`, with includes and named materials spelled out.
+    pub fn save(&self, path: &Path, format: Format) -> anyhow::Result<()> {
+        let text = format.write(self)?;
+        fs::write(path, text).with_context(|| format!("writing {}", path.display()))
+    }
+
+    pub fn animation(&self) -> Option<&Animation> {
+        self.animation.as_ref()
+    }
+
+    /// The entities written out, followed by the ones the generators expand into.
+    fn entities(&self) -> anyhow::Result<Vec<EntityConfig>> {
+        let mut entities = self.entity.clone();
+
+        for (index, generator) in self.generate.iter().enumerate() {
+            let generated = generator
+                .expand()
+                .with_context(|| format!("expanding generate[{index}]"))?;
+            entities.extend(generated);
+        }
+
+        Ok(entities)
+    }
+
+    /// Files the scene is loaded from besides the config itself.
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        let templates = self.generate.iter().map(|generator| &generator.entity);
+        let entities: Vec<_> = self.entity.iter().chain(templates).collect();
+
+        let textures = entities
+            .iter()
+            .filter_map(|entity| entity.material()?.color.path());
+
+        let grids = self.volume.iter().filter_map(|volume| match volume {
+            VolumeConfig::Grid { path, .. } => Some(path.as_path()),
+            VolumeConfig::Homogeneous(_) => None,
+        });
+
+        let models = entities.iter().filter_map(|entity| entity.path());
+
+        let files = textures.chain(models).chain(grids).map(PathBuf::from);
+        self.includes.iter().cloned().chain(files).collect()
+    }
+
+    /// Returns a copy of the config with every animation track applied at `frame`.
+    pub fn at_frame(&self, frame: f32) -> anyhow::Result<WorldConfig> {
+        let mut config = self.clone();
+
+        if let Some(animation) = &self.animation {
+            for (target, value) in animation.sample(frame) {
+                config
+                    .apply(target, value)
+                    .with_context(|| format!("animating {target:?}"))?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn apply(&mut self, target: &Target, value: Vec3) -> anyhow::Result<()> {
+        match *target {
+            Target::Camera(property) => self.camera.apply(property, value),
+            Target::Entity(index, property) => self
+                .entity
+                .get_mut(index)
+                .with_context(|| format!("there is no entity {index}"))?
+                .apply(property, value)?,
+            Target::Light(index, property) => self
+                .light
+                .get_mut(index)
+                .with_context(|| format!("there is no light {index}"))?
+                .apply(property, value)?,
+        }
+
+        Ok(())
+    }
+
+    /// Builds the scene at `frame`. While the shutter is open, entities and the camera move
+    /// from where the animation puts them at shutter open to where it puts them at shutter
+    /// close, plus any constant `velocity`. Parts of the scene that were skipped while
+    /// building it, such as unsupported features of a glTF model, are returned as warnings.
+    pub fn build(&self, frame: f32) -> anyhow::Result<(Scene, Vec<Diagnostic>)> {
+        let open = self.at_frame(frame + self.shutter.open)?;
+        let close = self.at_frame(frame + self.shutter.close)?;
+        let exposure = self.shutter.close - self.shutter.open;
+
+        let entities = open.entities()?;
+        let ends = close.entities()?;
+        let mut world = World::new();
+        let mut warnings = Vec::new();
+        let mut ids = MaterialIds::default();
+
+        world.reflection_depth = open.reflection_depth;
+        world.reflection_samples = open.reflection_samples;
+        world.sky = open.sky;
+        world.fog = open.fog;
+
+        for light in open.light {
+            world.lights.push(light.build()?);
+        }
+
+        for (mut entity, end) in entities.into_iter().zip(&ends) {
+            let motion = end.position() - entity.position() + entity.velocity() * exposure;
+            if let Some(material) = entity.material_mut() {
+                ids.assign(material);
+            }
+            world
+                .entities
+                .push(entity.build(motion, &mut ids, &mut warnings)?);
+        }
+
+        for volume in open.volume {
+            world.volumes.push(volume.build()?);
+        }
+
+        let mut camera = open.camera.build();
+        camera.motion = close.camera.origin - camera.origin;
+
+        let mut scene = Scene::new(camera, world);
+        scene.tone_mapping = open.tone_mapping;
+        scene.denoiser = open.denoise;
+
+        Ok((scene, warnings))
+    }
+}
+
+/// Reads an entry saved from a built scene back into its config.
+fn load<T: for<'de> Deserialize<'de>>(
+    kind: &str,
+    index: usize,
+    entry: Option<Value>,
+) -> anyhow::Result<T> {
+    let entry = entry.with_context(|| format!("{kind} {index} cannot be saved"))?;
+    serde_yaml::from_value(entry).with_context(|| format!("saving {kind} {index}"))
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct CameraConfig {
+/// The part of a frame during which the shutter is open, in frames relative to the frame
+/// being rendered. The default of an instant exposure disables motion blur.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct Shutter {
+    #[serde(default)]
+    open: f32,
+    #[serde(default)]
+    close: f32,
+}
+
+impl Shutter {
+    fn is_instant(&self) -> bool {
+        self.open == self.close
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct CameraConfig {
     width: u32,
     height: u32,
     #[serde(default = "CameraConfig::fov")]
     fov: f32,
     #[serde(default)]
     origin: Vec3,
+    /// Degrees to the right of looking down -z.
+    #[serde(default)]
+    yaw: f32,
+    /// Degrees above the horizon.
+    #[serde(default)]
+    pitch: f32,
     #[serde(default = "CameraConfig::speed")]
     speed: f32,
 }
 
+impl From<&Camera> for CameraConfig {
+    fn from(camera: &Camera) -> Self {
+        CameraConfig {
+            width: camera.viewport_width as u32,
+            height: camera.viewport_height as u32,
+            fov: (camera.fov.atan() * 2.0).to_degrees(),
+            origin: camera.origin,
+            yaw: camera.yaw.to_degrees(),
+            pitch: camera.pitch.to_degrees(),
+            speed: camera.speed,
+        }
+    }
+}
+
 impl CameraConfig {
     fn build(self) -> Camera {
         Camera {
-            viewport_width: self.width as f32,
-            viewport_height: self.height as f32,
-            fov: Camera::compute_fov(self.fov),
-            origin: self.origin,
             speed: self.speed,
+            ..Camera::new(self.width, self.height)
+                .with_fov(self.fov)
+                .with_origin(self.origin)
+                .with_rotation(self.yaw, self.pitch)
+        }
+    }
+
+    fn apply(&mut self, property: CameraProperty, value: Vec3) {
+        match property {
+            CameraProperty::Origin => self.origin = value,
+            CameraProperty::Yaw => self.yaw = value.x,
+            CameraProperty::Pitch => self.pitch = value.x,
+            CameraProperty::Fov => self.fov = value.x,
         }
     }
 
@@ -85,27 +471,95 @@ impl CameraConfig {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(remote = "Self")]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 enum LightConfig {
     Directional(DirectionalLight),
     Point(PointLight),
+    Spot(SpotLight),
+    /// A type added through [`crate::registry`].
+    #[serde(skip)]
+    Custom(Custom),
 }
 
-impl LightConfig {
-    fn build(self) -> Box<dyn Light> {
+impl Serialize for LightConfig {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
         match self {
+            LightConfig::Custom(custom) => custom.serialize(serializer),
+            config => LightConfig::serialize(config, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LightConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+
+        match Custom::light(&value) {
+            Some(custom) => custom
+                .map(LightConfig::Custom)
+                .map_err(|e| D::Error::custom(format!("{e:#}"))),
+            None => LightConfig::deserialize(value).map_err(D::Error::custom),
+        }
+    }
+}
+
+impl LightConfig {
+    fn build(self) -> anyhow::Result<Box<dyn Light>> {
+        Ok(match self {
             LightConfig::Directional(mut light) => {
                 light.direction = light.direction.normalized();
                 Box::new(light)
             }
             LightConfig::Point(light) => Box::new(light),
+            LightConfig::Spot(mut light) => {
+                light.direction = light.direction.normalized();
+                Box::new(light)
+            }
+            LightConfig::Custom(custom) => custom.build_light()?,
+        })
+    }
+
+    fn apply(&mut self, property: LightProperty, value: Vec3) -> anyhow::Result<()> {
+        match (self, property) {
+            (LightConfig::Custom(custom), property) => {
+                bail!("`{}` lights cannot animate {property:?}", custom.kind)
+            }
+            (LightConfig::Directional(light), LightProperty::Direction) => light.direction = value,
+            (LightConfig::Directional(light), LightProperty::Color) => {
+                light.color = Color::from(<[f32; 3]>::from(value))
+            }
+            (LightConfig::Directional(light), LightProperty::Intensity) => {
+                light.intensity = value.x
+            }
+            (LightConfig::Point(light), LightProperty::Position) => light.position = value,
+            (LightConfig::Point(light), LightProperty::Color) => {
+                light.color = Color::from(<[f32; 3]>::from(value))
+            }
+            (LightConfig::Point(light), LightProperty::Intensity) => light.intensity = value.x,
+            (LightConfig::Spot(light), LightProperty::Position) => light.position = value,
+            (LightConfig::Spot(light), LightProperty::Direction) => light.direction = value,
+            (LightConfig::Spot(light), LightProperty::Color) => {
+                light.color = Color::from(<[f32; 3]>::from(value))
+            }
+            (LightConfig::Spot(light), LightProperty::Intensity) => light.intensity = value.x,
+            (_, property) => bail!("this light has no {property:?}"),
         }
+
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(remote = "Self")]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 enum EntityConfig {
@@ -113,36 +567,810 @@ enum EntityConfig {
         material: Material,
         position: Vec3,
         radius: f32,
+        /// Distance moved per frame while the shutter is open.
+        #[serde(default)]
+        velocity: Vec3,
     },
     Plane {
         material: Material,
         position: Vec3,
         normal: Vec3,
+        #[serde(default)]
+        velocity: Vec3,
     },
+    /// A PLY or STL mesh, scaled and then moved to `position`. Vertex colors in PLY files
+    /// take the place of the material's color.
+    Mesh {
+        material: Material,
+        path: PathBuf,
+        #[serde(default)]
+        position: Vec3,
+        #[serde(default = "EntityConfig::scale")]
+        scale: f32,
+        #[serde(default)]
+        velocity: Vec3,
+    },
+    /// The meshes of a glTF file as one entity, turned y-down, scaled and then moved to
+    /// `position`. Each mesh keeps its own material.
+    Gltf {
+        path: PathBuf,
+        #[serde(default)]
+        position: Vec3,
+        #[serde(default = "EntityConfig::scale")]
+        scale: f32,
+        #[serde(default)]
+        velocity: Vec3,
+    },
+    /// A type added through [`crate::registry`]. These don't move while the shutter is open.
+    #[serde(skip)]
+    Custom(Custom),
+}
+
+impl Serialize for EntityConfig {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            EntityConfig::Custom(custom) => custom.serialize(serializer),
+            config => EntityConfig::serialize(config, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EntityConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+
+        match Custom::entity(&value) {
+            Some(custom) => custom
+                .map(EntityConfig::Custom)
+                .map_err(|e| D::Error::custom(format!("{e:#}"))),
+            None => EntityConfig::deserialize(value).map_err(D::Error::custom),
+        }
+    }
 }
 
 impl EntityConfig {
-    fn build(self) -> Box<dyn Entity> {
+    /// The material of a built-in entity. Models and custom entities keep theirs to themselves.
+    fn material(&self) -> Option<&Material> {
+        match self {
+            EntityConfig::Sphere { material, .. }
+            | EntityConfig::Plane { material, .. }
+            | EntityConfig::Mesh { material, .. } => Some(material),
+            EntityConfig::Gltf { .. } | EntityConfig::Custom(_) => None,
+        }
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        match self {
+            EntityConfig::Sphere { material, .. }
+            | EntityConfig::Plane { material, .. }
+            | EntityConfig::Mesh { material, .. } => Some(material),
+            EntityConfig::Gltf { .. } | EntityConfig::Custom(_) => None,
+        }
+    }
+
+    /// The model file of an entity loaded from one.
+    fn path(&self) -> Option<&Path> {
+        match self {
+            EntityConfig::Mesh { path, .. } | EntityConfig::Gltf { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    fn scale() -> f32 {
+        1.0
+    }
+
+    fn apply(&mut self, property: EntityProperty, value: Vec3) -> anyhow::Result<()> {
+        match (self, property) {
+            (EntityConfig::Custom(custom), property) => {
+                bail!("`{}` entities cannot animate {property:?}", custom.kind)
+            }
+            (EntityConfig::Sphere { position, .. }, EntityProperty::Position)
+            | (EntityConfig::Plane { position, .. }, EntityProperty::Position)
+            | (EntityConfig::Mesh { position, .. }, EntityProperty::Position)
+            | (EntityConfig::Gltf { position, .. }, EntityProperty::Position) => *position = value,
+            (EntityConfig::Sphere { radius, .. }, EntityProperty::Radius) => *radius = value.x,
+            (EntityConfig::Plane { normal, .. }, EntityProperty::Normal) => *normal = value,
+            (
+                EntityConfig::Sphere { material, .. }
+                | EntityConfig::Plane { material, .. }
+                | EntityConfig::Mesh { material, .. },
+                EntityProperty::Color,
+            ) => {
+                let color = Color::from(<[f32; 3]>::from(value));
+                material.color = Coloration::Color(color);
+            }
+            (
+                EntityConfig::Sphere { material, .. }
+                | EntityConfig::Plane { material, .. }
+                | EntityConfig::Mesh { material, .. },
+                EntityProperty::Albedo,
+            ) => material.albedo = value.x,
+            (_, property) => bail!("this entity has no {property:?}"),
+        }
+
+        Ok(())
+    }
+
+    fn position(&self) -> Vec3 {
         match self {
+            EntityConfig::Sphere { position, .. }
+            | EntityConfig::Plane { position, .. }
+            | EntityConfig::Mesh { position, .. }
+            | EntityConfig::Gltf { position, .. } => *position,
+            EntityConfig::Custom(_) => Vec3::zero(),
+        }
+    }
+
+    fn velocity(&self) -> Vec3 {
+        match self {
+            EntityConfig::Sphere { velocity, .. }
+            | EntityConfig::Plane { velocity, .. }
+            | EntityConfig::Mesh { velocity, .. }
+            | EntityConfig::Gltf { velocity, .. } => *velocity,
+            EntityConfig::Custom(_) => Vec3::zero(),
+        }
+    }
+
+    fn build(
+        self,
+        motion: Vec3,
+        ids: &mut MaterialIds,
+        warnings: &mut Vec<Diagnostic>,
+    ) -> anyhow::Result<Box<dyn Entity>> {
+        // Meshes and models keep their entry, since the triangles they are built from
+        // cannot be written back as one.
+        let source = match &self {
+            EntityConfig::Mesh { .. } | EntityConfig::Gltf { .. } => {
+                Some(serde_yaml::to_value(&self)?)
+            }
+            _ => None,
+        };
+
+        Ok(match self {
             EntityConfig::Sphere {
                 position: center,
                 radius,
                 material,
+                ..
             } => Box::new(Sphere {
                 center,
                 radius,
                 material,
+                motion,
             }),
 
             EntityConfig::Plane {
                 position,
                 normal,
                 material,
+                ..
             } => Box::new(Plane {
                 position,
-                normal,
+                normal: normal.normalized(),
                 material,
+                motion,
             }),
+
+            EntityConfig::Mesh {
+                material,
+                path,
+                position,
+                scale,
+                ..
+            } => {
+                let mut data = import::mesh(&path)?;
+                data.transform(Mat4::from_translation(position) * Mat4::from_scale(scale));
+
+                let mut mesh = Mesh::new(data, material)
+                    .with_context(|| format!("invalid mesh {}", path.display()))?;
+                mesh.motion = motion;
+                mesh.source = source;
+                Box::new(mesh)
+            }
+
+            EntityConfig::Gltf {
+                path,
+                position,
+                scale,
+                ..
+            } => {
+                let transform = Mat4::from_translation(position) * Mat4::from_scale(scale);
+                let (mut group, skipped) = gltf::model(&path, transform)?;
+                warnings.extend(skipped.into_iter().map(|mut warning| {
+                    warning.path = format!("{}: {}", path.display(), warning.path);
+                    warning
+                }));
+
+                // The model numbers its materials by their index in the file.
+                let count = group.meshes().iter().map(|mesh| mesh.material.id + 1).max();
+                let first = ids.reserve(count.unwrap_or(0));
+                for mesh in group.meshes_mut() {
+                    mesh.material.id += first;
+                }
+
+                group.set_motion(motion);
+                group.source = source;
+                Box::new(group)
+            }
+
+            EntityConfig::Custom(custom) => custom.build_entity()?,
+        })
+    }
+}
+
+/// Copies of `entity` laid out by a rule instead of written one by one, with their materials
+/// varied within `material`. The layout's positions are added to the template's own.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct GeneratorConfig {
+    #[serde(flatten)]
+    layout: Layout,
+    entity: EntityConfig,
+    /// Seeds every random choice, so a config always expands into the same entities.
+    #[serde(default)]
+    seed: u64,
+    #[serde(default, skip_serializing_if = "MaterialRanges::is_empty")]
+    material: MaterialRanges,
+}
+
+impl GeneratorConfig {
+    fn expand(&self) -> anyhow::Result<Vec<EntityConfig>> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let positions = self.layout.positions(&mut rng)?;
+
+        positions
+            .into_iter()
+            .map(|position| {
+                let mut entity = self.entity.clone();
+                let position = entity.position() + position;
+                entity.apply(EntityProperty::Position, position)?;
+
+                if !self.material.is_empty() {
+                    let material = entity
+                        .material_mut()
+                        .context("only entities with a `material` can vary it")?;
+                    self.material.apply(material, &mut rng);
+                }
+
+                Ok(entity)
+            })
+            .collect()
+    }
+}
+
+/// Most entities a single generator may add, so that a mistyped count fails instead of
+/// exhausting memory.
+pub(crate) const MAX_GENERATED: u64 = 1_000_000;
+
+/// Where a generator puts its entities. Layouts lie flat in the xz plane, since y points down.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+enum Layout {
+    /// `count[0]` by `count[1]` entities `spacing` apart along x and z, starting at `origin`.
+    /// Each is moved a random distance of up to `jitter` times the spacing along both axes.
+    Grid {
+        count: [u32; 2],
+        spacing: f32,
+        #[serde(default)]
+        origin: Vec3,
+        #[serde(default)]
+        jitter: f32,
+    },
+    /// `count` entities at random in the box from `min` to `max`, at least `spacing` apart.
+    Scatter {
+        count: u32,
+        min: Vec3,
+        max: Vec3,
+        #[serde(default)]
+        spacing: f32,
+    },
+    /// `count` entities evenly spaced around a circle.
+    Circle {
+        count: u32,
+        center: Vec3,
+        radius: f32,
+    },
+    /// `count` entities along a spiral that winds `turns` times from `center` out to `radius`.
+    Spiral {
+        count: u32,
+        center: Vec3,
+        radius: f32,
+        turns: f32,
+    },
+}
+
+impl Layout {
+    /// How many times each scattered entity is tried before giving up on the spacing.
+    const SCATTER_ATTEMPTS: u32 = 100;
+
+    fn count(&self) -> u64 {
+        match *self {
+            Layout::Grid {
+                count: [columns, rows],
+                ..
+            } => u64::from(columns) * u64::from(rows),
+            Layout::Scatter { count, .. }
+            | Layout::Circle { count, .. }
+            | Layout::Spiral { count, .. } => u64::from(count),
+        }
+    }
+
+    fn positions(&self, rng: &mut StdRng) -> anyhow::Result<Vec<Vec3>> {
+        ensure!(
+            self.count() <= MAX_GENERATED,
+            "{} entities are more than the {MAX_GENERATED} a generator can add",
+            self.count()
+        );
+
+        Ok(match *self {
+            Layout::Grid {
+                count: [columns, rows],
+                spacing,
+                origin,
+                jitter,
+            } => {
+                let mut positions = Vec::new();
+                for row in 0..rows {
+                    for column in 0..columns {
+                        let cell = Vec3::new(column as f32, 0.0, row as f32);
+                        let offset = Vec3::new(rng.gen(), 0.0, rng.gen()) * jitter;
+                        positions.push(origin + (cell + offset) * spacing);
+                    }
+                }
+                positions
+            }
+
+            Layout::Scatter {
+                count,
+                min,
+                max,
+                spacing,
+            } => {
+                let mut positions: Vec<Vec3> = Vec::new();
+                for _ in 0..u64::from(count) * u64::from(Layout::SCATTER_ATTEMPTS) {
+                    if positions.len() == count as usize {
+                        break;
+                    }
+
+                    let t = Vec3::new(rng.gen(), rng.gen(), rng.gen());
+                    let position = min + (max - min) * t;
+                    if positions
+                        .iter()
+                        .all(|other| (*other - position).mag() >= spacing)
+                    {
+                        positions.push(position);
+                    }
+                }
+
+                ensure!(
+                    positions.len() == count as usize,
+                    "only {} of {count} entities fit {spacing} apart",
+                    positions.len()
+                );
+                positions
+            }
+
+            Layout::Circle {
+                count,
+                center,
+                radius,
+            } => (0..count)
+                .map(|i| {
+                    let angle = TAU * i as f32 / count as f32;
+                    center + Vec3::new(angle.cos(), 0.0, angle.sin()) * radius
+                })
+                .collect(),
+
+            Layout::Spiral {
+                count,
+                center,
+                radius,
+                turns,
+            } => (0..count)
+                .map(|i| {
+                    let t = i as f32 / count.saturating_sub(1).max(1) as f32;
+                    let angle = TAU * turns * t;
+                    center + Vec3::new(angle.cos(), 0.0, angle.sin()) * radius * t
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Ranges that generated materials are picked from, each written as `[min, max]`. Anything
+/// left out keeps the template's value.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct MaterialRanges {
+    /// Picks each channel between the two colors on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<[Color; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    albedo: Option<[f32; 2]>,
+    /// Makes every material reflective.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reflectivity: Option<[f32; 2]>,
+    /// Only changes reflective materials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    roughness: Option<[f32; 2]>,
+}
+
+impl MaterialRanges {
+    fn is_empty(&self) -> bool {
+        self.color.is_none()
+            && self.albedo.is_none()
+            && self.reflectivity.is_none()
+            && self.roughness.is_none()
+    }
+
+    fn apply(&self, material: &mut Material, rng: &mut StdRng) {
+        let mut pick = |[min, max]: [f32; 2]| min + (max - min) * rng.gen::<f32>();
+
+        if let Some([from, to]) = self.color {
+            let [from, to] = [from, to].map(<[f32; 3]>::from);
+            let channels: [f32; 3] = std::array::from_fn(|i| pick([from[i], to[i]]));
+            material.color = Coloration::Color(Color::from(channels));
+        }
+
+        if let Some(range) = self.albedo {
+            material.albedo = pick(range);
         }
+
+        if let Some(range) = self.reflectivity {
+            let roughness = match material.surface {
+                SurfaceType::Reflective { roughness, .. } => roughness,
+                SurfaceType::Diffuse => 0.0,
+            };
+            material.surface = SurfaceType::Reflective {
+                reflectivity: pick(range),
+                roughness,
+            };
+        }
+
+        if let (Some(range), SurfaceType::Reflective { roughness, .. }) =
+            (self.roughness, &mut material.surface)
+        {
+            *roughness = pick(range);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+enum VolumeConfig {
+    Homogeneous(HomogeneousVolume),
+    Grid {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<Vec3>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<Vec3>,
+        #[serde(default = "VolumeConfig::density")]
+        density: f32,
+        absorption: f32,
+        scattering: f32,
+        #[serde(default)]
+        anisotropy: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        emission: Option<Emission>,
+        #[serde(default = "VolumeConfig::samples")]
+        samples: usize,
+    },
+}
+
+impl VolumeConfig {
+    fn build(self) -> anyhow::Result<Box<dyn Volume>> {
+        Ok(match self {
+            VolumeConfig::Homogeneous(volume) => Box::new(volume),
+
+            VolumeConfig::Grid {
+                path,
+                min,
+                max,
+                density,
+                absorption,
+                scattering,
+                anisotropy,
+                emission,
+                samples,
+            } => {
+                let mut grid = Grid::open(&path)?;
+                grid.min = min.unwrap_or(grid.min);
+                grid.max = max.unwrap_or(grid.max);
+
+                if let Some(emission) = &emission {
+                    ensure!(
+                        emission.channel < grid.channels(),
+                        "{} has no channel {} for emission",
+                        path.display(),
+                        emission.channel,
+                    );
+                }
+
+                Box::new(HeterogeneousVolume::new(
+                    grid, density, absorption, scattering, anisotropy, emission, samples,
+                ))
+            }
+        })
+    }
+
+    fn density() -> f32 {
+        1.0
+    }
+
+    fn samples() -> usize {
+        4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use ultraviolet::Vec3;
+
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::format::Format;
+    use crate::light::point::PointLight;
+    use crate::model::{Material, SurfaceType};
+    use crate::ray::{Intersectable, Ray};
+    use crate::registry::Registry;
+    use crate::scene::Scene;
+    use crate::world::World;
+
+    use super::{parse_config, save_camera, save_config, Layout, WorldConfig};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn configs_survive_saving() {
+        for name in [
+            "config",
+            "fog",
+            "library",
+            "meshes",
+            "smoke",
+            "spheres",
+            "turntable",
+        ] {
+            let (config, _) = WorldConfig::open(
+                Path::new(&format!("assets/{name}.yaml")),
+                Format::Yaml,
+                &Registry::default(),
+            )
+            .unwrap();
+            let saved = serde_yaml::to_value(&config).unwrap();
+            let reloaded: WorldConfig = serde_yaml::from_value(saved.clone()).unwrap();
+
+            assert_eq!(serde_yaml::to_value(&reloaded).unwrap(), saved, "{name}");
+        }
+    }
+
+    #[test]
+    fn scenes_built_in_code_can_be_saved() {
+        let world = World::builder()
+            .sphere(
+                Vec3::new(0.0, 0.0, -3.0),
+                1.0,
+                Material::new(Color::from_hex(0xFF8000), 0.8).reflective(0.5, 0.1),
+            )
+            .plane(
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::unit_y(),
+                Material::new(Color::new(0.3, 0.6, 2.0), 0.5),
+            )
+            .light(PointLight::new(
+                Vec3::zero(),
+                Color::new(1.0, 1.0, 1.0),
+                50.0,
+            ))
+            .build();
+        let camera = Camera::new(32, 24).with_rotation(30.0, -10.0);
+        let scene = Scene::new(camera, world);
+
+        let path = temp_dir("save-test").join("scene.yaml");
+        save_config(&scene, &path).unwrap();
+        let reloaded = parse_config(&path).unwrap();
+
+        assert_eq!(reloaded.world.entities.len(), 2);
+        assert_eq!(reloaded.world.lights.len(), 1);
+        assert!((reloaded.camera.yaw - scene.camera.yaw).abs() < 1e-6);
+
+        let material = reloaded.world.entities[0].material();
+        assert_eq!(
+            material.color(Default::default()),
+            Color::from_hex(0xFF8000)
+        );
+
+        let material = reloaded.world.entities[1].material();
+        assert_eq!(
+            material.color(Default::default()),
+            Color::new(0.3, 0.6, 2.0)
+        );
+    }
+
+    #[test]
+    fn entities_sharing_a_material_share_its_id() {
+        let path = temp_dir("material-id-test").join("scene.yaml");
+        let tile = std::env::current_dir().unwrap().join("assets/tile.png");
+        let config = format!(
+            "\
+camera: {{ width: 16, height: 16 }}
+materials:
+  matte: {{ albedo: 0.8, color: 0x808080 }}
+  shiny: {{ albedo: 0.2, color: 0xFFFFFF, surface: {{ reflectivity: 0.9 }} }}
+  tiled: {{ color: {{ path: {tile:?}, scale: 2 }} }}
+light: []
+entity:
+  - {{ type: sphere, position: [0, 0, -4], radius: 1, material: matte }}
+  - {{ type: sphere, position: [2, 0, -4], radius: 1, material: shiny }}
+  - {{ type: plane, position: [0, 1, 0], normal: [0, 1, 0], material: matte }}
+  - {{ type: sphere, position: [4, 0, -4], radius: 1, material: {{ albedo: 0.8, color: 0x808080 }} }}
+  - {{ type: sphere, position: [6, 0, -4], radius: 1, material: {{ base: matte, albedo: 0.3 }} }}
+  - {{ type: sphere, position: [8, 0, -4], radius: 1, material: tiled }}
+  - {{ type: sphere, position: [10, 0, -4], radius: 1, material: tiled }}
+  - {{ type: sphere, position: [12, 0, -4], radius: 1, material: {{ base: shiny, surface: {{ reflectivity: 0.9, roughness: 0.1 }} }} }}
+"
+        );
+        std::fs::write(&path, config).unwrap();
+
+        let scene = parse_config(&path).unwrap();
+        let ids: Vec<_> = scene
+            .world
+            .entities
+            .iter()
+            .map(|entity| entity.material().id)
+            .collect();
+        assert_eq!(ids, [0, 1, 0, 0, 2, 3, 3, 4]);
+
+        let matte = Material::new(Color::new(0.5, 0.5, 0.5), 0.8);
+        let world = World::builder()
+            .sphere(Vec3::new(0.0, 0.0, -4.0), 1.0, matte.clone())
+            .sphere(Vec3::new(2.0, 0.0, -4.0), 1.0, Material::default())
+            .plane(Vec3::new(0.0, 1.0, 0.0), Vec3::unit_y(), matte)
+            .build();
+        let ids: Vec<_> = world
+            .entities
+            .iter()
+            .map(|entity| entity.material().id)
+            .collect();
+        assert_eq!(ids, [0, 1, 0]);
+    }
+
+    #[test]
+    fn mesh_entities_load_ply_and_stl_files() {
+        let scene = parse_config(Path::new("assets/meshes.yaml")).unwrap();
+        assert_eq!(scene.world.entities.len(), 3);
+
+        let path = temp_dir("mesh-save-test").join("meshes.yaml");
+        save_config(&scene, &path).unwrap();
+        let scene = parse_config(&path).unwrap();
+        assert_eq!(scene.world.entities.len(), 3);
+
+        let cube = Vec3::new(-1.0, 0.0, -5.0);
+        let ray = Ray::new(Vec3::zero(), cube.normalized());
+        let hit = scene.world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        assert_eq!(hit.entity, 0);
+        assert!((hit.hit.z + 4.5).abs() < 1e-4);
+        assert_eq!(hit.color(), Color::from_hex(0xE0A060));
+
+        let tetrahedron = Vec3::new(0.7, -0.3, -4.0);
+        let ray = Ray::new(Vec3::zero(), tetrahedron.normalized());
+        let hit = scene.world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        assert_eq!(hit.entity, 1);
+    }
+
+    #[test]
+    fn generators_expand_after_written_entities() {
+        let (config, _) = WorldConfig::open(
+            Path::new("assets/spheres.yaml"),
+            Format::Yaml,
+            &Registry::default(),
+        )
+        .unwrap();
+        let entities = config.entities().unwrap();
+
+        assert_eq!(entities.len(), 4 + 22 * 22 + 12);
+        assert_eq!(
+            serde_yaml::to_value(&entities).unwrap(),
+            serde_yaml::to_value(config.entities().unwrap()).unwrap()
+        );
+
+        for entity in &entities[4..4 + 22 * 22] {
+            let position = entity.position();
+            assert_eq!(position.y, -0.2);
+            assert!((-11.0..11.0).contains(&position.x));
+
+            let albedo = entity.material().unwrap().albedo;
+            assert!((0.5..=1.0).contains(&albedo));
+        }
+
+        for entity in &entities[4 + 22 * 22..] {
+            let offset = entity.position() - Vec3::new(0.0, -0.3, 0.0);
+            assert!((offset.mag() - 2.3).abs() < 1e-5);
+            assert!(matches!(
+                entity.material().unwrap().surface,
+                SurfaceType::Reflective { reflectivity, roughness }
+                    if (0.3..=0.9).contains(&reflectivity) && (0.0..=0.3).contains(&roughness)
+            ));
+        }
+    }
+
+    #[test]
+    fn scattered_entities_keep_their_distance() {
+        let layout = |spacing| Layout::Scatter {
+            count: 40,
+            min: Vec3::zero(),
+            max: Vec3::new(10.0, 0.0, 10.0),
+            spacing,
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let positions = layout(1.0).positions(&mut rng).unwrap();
+        assert_eq!(positions.len(), 40);
+        for (i, a) in positions.iter().enumerate() {
+            for b in &positions[i + 1..] {
+                assert!((*a - *b).mag() >= 1.0);
+            }
+        }
+
+        assert!(layout(5.0).positions(&mut rng).is_err());
+    }
+
+    #[test]
+    fn huge_counts_are_errors() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let scatter = Layout::Scatter {
+            count: u32::MAX,
+            min: Vec3::zero(),
+            max: Vec3::one(),
+            spacing: 0.0,
+        };
+        assert!(scatter.positions(&mut rng).is_err());
+
+        let grid = Layout::Grid {
+            count: [100_000, 100_000],
+            spacing: 1.0,
+            origin: Vec3::zero(),
+            jitter: 0.0,
+        };
+        assert!(grid.positions(&mut rng).is_err());
+    }
+
+    #[test]
+    fn camera_pose_is_saved_to_a_copy() {
+        let path = temp_dir("camera-test").join("scene.yaml");
+        let original = "\
+camera: { width: 16, height: 16, fov: 45 }
+light: []
+entity: []
+";
+        std::fs::write(&path, original).unwrap();
+
+        let camera = Camera::new(16, 16)
+            .with_origin(Vec3::new(1.0, -2.0, 3.0))
+            .with_rotation(90.0, 0.0);
+        let copy = save_camera(&path, Format::Yaml, &camera).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        let (config, _) = WorldConfig::open(&copy, Format::Yaml, &Registry::default()).unwrap();
+        assert_eq!(config.camera.origin, Vec3::new(1.0, -2.0, 3.0));
+        assert_eq!(config.camera.yaw, 90.0);
+        assert_eq!(config.camera.fov, 45.0);
     }
 }
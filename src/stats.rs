@@ -0,0 +1,84 @@
+//! Counts of the rays traced and intersections tested while rendering, for benchmarks. Each
+//! thread keeps its own counts, which are cheap enough to always keep.
+
+use std::cell::Cell;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    /// Rays from the camera.
+    pub primary_rays: u64,
+    /// Rays toward lights, from surfaces and from inside volumes.
+    pub shadow_rays: u64,
+    /// Mirror and glossy reflection rays.
+    pub reflection_rays: u64,
+    /// Entities tested against a ray, plus the primitives tested in the leaves of a BVH.
+    pub intersection_tests: u64,
+    /// BVH nodes whose bounds a ray entered.
+    pub bvh_node_visits: u64,
+}
+
+impl Stats {
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.reflection_rays
+    }
+}
+
+thread_local! {
+    static STATS: Cell<Stats> = Cell::new(Stats::default());
+}
+
+/// Returns what the current thread counted since the last call, and starts again from zero.
+pub fn take() -> Stats {
+    STATS.with(Cell::take)
+}
+
+pub(crate) fn count(update: impl FnOnce(&mut Stats)) {
+    STATS.with(|stats| {
+        let mut counts = stats.get();
+        update(&mut counts);
+        stats.set(counts);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::light::point::PointLight;
+    use crate::{render_buffer, Camera, Color, Material, RenderSettings, Scene, World};
+
+    use super::take;
+
+    #[test]
+    fn renders_count_every_ray_they_trace() {
+        let world = World::builder()
+            .sphere(
+                Vec3::new(0.0, 0.0, -3.0),
+                1.0,
+                Material::new(Color::new(0.8, 0.1, 0.1), 0.8),
+            )
+            .light(PointLight::new(
+                Vec3::new(2.0, -2.0, 0.0),
+                Color::new(1.0, 1.0, 1.0),
+                100.0,
+            ))
+            .build();
+        let scene = Scene::new(Camera::new(4, 4), world);
+        let settings = RenderSettings {
+            samples: 2,
+            ..RenderSettings::default()
+        };
+
+        take();
+        render_buffer(&scene, &settings);
+        let first = take();
+        render_buffer(&scene, &settings);
+
+        assert_eq!(first.primary_rays, 32);
+        assert!(first.shadow_rays > 0);
+        assert!(first.intersection_tests >= first.rays());
+        assert_eq!(take(), first);
+    }
+}
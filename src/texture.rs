@@ -1,16 +1,33 @@
-use std::path::PathBuf;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use image::{DynamicImage, GenericImageView};
-use serde::{Deserialize, Deserializer};
-use serde::de::{Error, MapAccess, Visitor};
+use image::{DynamicImage, ImageResult, Rgb, Rgb32FImage};
+use serde::de::Error;
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::Value;
 
-use crate::color::Color;
+use crate::color::{Color, ColorSpace};
 use crate::model::TextureCoord;
+use crate::registry;
 
 #[derive(Debug, Clone)]
 pub enum Coloration {
     Color(Color),
     Texture(ImageTexture),
+    Procedural(Arc<dyn ProceduralTexture>),
+}
+
+/// A color computed from the texture coordinate, loaded from configs once registered with
+/// [`registry::Registry::register_texture`].
+pub trait ProceduralTexture: Debug + Send + Sync {
+    fn get(&self, uv: &TextureCoord) -> Color;
+
+    /// The config entry that loads this texture, used when saving scenes. Textures that
+    /// cannot be written to a config return `None`.
+    fn to_config(&self) -> Option<Value> {
+        None
+    }
 }
 
 impl Coloration {
@@ -18,6 +35,7 @@ impl Coloration {
         match self {
             Coloration::Color(color) => *color,
             Coloration::Texture(texture) => texture.get(uv),
+            Coloration::Procedural(texture) => texture.get(uv),
         }
     }
 
@@ -25,8 +43,48 @@ impl Coloration {
         Coloration::Color(Color::from_hex(hex))
     }
 
-    pub fn texture(scale: f32, path: impl Into<PathBuf>) -> Coloration {
-        Coloration::Texture(ImageTexture::new(scale, path))
+    pub fn texture(
+        scale: f32,
+        path: impl Into<PathBuf>,
+        color_space: ColorSpace,
+    ) -> ImageResult<Coloration> {
+        ImageTexture::new(scale, path, color_space).map(Coloration::Texture)
+    }
+
+    /// What tells this coloration apart from others, or `None` if it cannot be compared,
+    /// such as a texture decoded from memory.
+    pub(crate) fn key(&self) -> Option<ColorationKey> {
+        Some(match self {
+            Coloration::Color(color) => {
+                ColorationKey::Color(<[f32; 3]>::from(*color).map(f32::to_bits))
+            }
+            Coloration::Texture(texture) => ColorationKey::Texture(
+                texture.path.clone()?,
+                texture.scale.to_bits(),
+                texture.color_space,
+            ),
+            Coloration::Procedural(texture) => ColorationKey::Procedural(texture.to_config()?),
+        })
+    }
+
+    /// The file this coloration was loaded from, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Coloration::Color(_) | Coloration::Procedural(_) => None,
+            Coloration::Texture(texture) => texture.path.as_deref(),
+        }
+    }
+}
+
+impl From<Color> for Coloration {
+    fn from(color: Color) -> Self {
+        Coloration::Color(color)
+    }
+}
+
+impl From<ImageTexture> for Coloration {
+    fn from(texture: ImageTexture) -> Self {
+        Coloration::Texture(texture)
     }
 }
 
@@ -35,18 +93,77 @@ impl<'de> serde::Deserialize<'de> for Coloration {
     where
         D: Deserializer<'de>,
     {
-        Ok(match ColorationData::deserialize(deserializer)? {
-            ColorationData::Color(c) => Coloration::Color(c),
-            ColorationData::Texture { path, scale } => Coloration::texture(scale, path),
-        })
+        let value = Value::deserialize(deserializer)?;
+
+        if let Some(kind) = registry::tag(&value) {
+            return match registry::texture(kind, &value) {
+                Some(texture) => texture
+                    .map(Coloration::Procedural)
+                    .map_err(|e| D::Error::custom(format!("{e:#}"))),
+                None => Err(D::Error::custom(format!("unknown texture type `{kind}`"))),
+            };
+        }
+
+        Ok(
+            match ColorationData::deserialize(value).map_err(D::Error::custom)? {
+                ColorationData::Color(c) => Coloration::Color(c),
+                ColorationData::Texture {
+                    path,
+                    scale,
+                    color_space,
+                } => Coloration::texture(scale, &path, color_space).map_err(|e| {
+                    D::Error::custom(format!("opening texture {}: {e}", path.display()))
+                })?,
+            },
+        )
+    }
+}
+
+impl Serialize for Coloration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Coloration::Color(color) => color.serialize(serializer),
+            Coloration::Texture(texture) => {
+                let path = texture.path.clone().ok_or_else(|| {
+                    ser::Error::custom("textures made from images in memory cannot be saved")
+                })?;
+
+                ColorationData::Texture {
+                    scale: texture.scale,
+                    path,
+                    color_space: texture.color_space,
+                }
+                .serialize(serializer)
+            }
+            Coloration::Procedural(texture) => texture
+                .to_config()
+                .ok_or_else(|| ser::Error::custom(format!("{texture:?} cannot be saved")))?
+                .serialize(serializer),
+        }
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// A coloration in a form that can be hashed, with numbers compared by their bits.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ColorationKey {
+    Color([u32; 3]),
+    Texture(PathBuf, u32, ColorSpace),
+    Procedural(Value),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 enum ColorationData {
     Color(Color),
-    Texture { scale: f32, path: PathBuf },
+    Texture {
+        scale: f32,
+        path: PathBuf,
+        #[serde(default)]
+        color_space: ColorSpace,
+    },
 }
 
 impl Default for Coloration {
@@ -55,24 +172,50 @@ impl Default for Coloration {
     }
 }
 
+/// An image decoded into linear floating point on load. Clones share the decoded pixels.
 #[derive(Debug, Clone)]
 pub struct ImageTexture {
-    texture: DynamicImage,
+    texture: Arc<Rgb32FImage>,
     scale: f32,
+    color_space: ColorSpace,
+    path: Option<PathBuf>,
 }
 
 impl ImageTexture {
-    pub fn new(scale: f32, path: impl Into<PathBuf>) -> ImageTexture {
+    pub fn new(
+        scale: f32,
+        path: impl Into<PathBuf>,
+        color_space: ColorSpace,
+    ) -> ImageResult<ImageTexture> {
         let path = path.into();
-        let texture = image::open(&path).expect("open texture file");
-        ImageTexture { texture, scale }
+        let image = image::open(&path)?;
+
+        Ok(ImageTexture {
+            path: Some(path),
+            ..ImageTexture::from_image(scale, image, color_space)
+        })
+    }
+
+    pub fn from_image(scale: f32, image: DynamicImage, color_space: ColorSpace) -> ImageTexture {
+        let mut texture = image.into_rgb32f();
+
+        for value in texture.iter_mut() {
+            *value = color_space.decode(*value);
+        }
+
+        ImageTexture {
+            texture: Arc::new(texture),
+            scale,
+            color_space,
+            path: None,
+        }
     }
 
     fn get(&self, uv: &TextureCoord) -> Color {
         let wrapped_x = wrap(uv.x / self.scale, self.texture.width());
         let wrapped_y = wrap(uv.y / self.scale, self.texture.height());
 
-        let pixel = self.texture.get_pixel(wrapped_x, wrapped_y);
+        let Rgb(pixel) = *self.texture.get_pixel(wrapped_x, wrapped_y);
         Color::from(pixel)
     }
 }
@@ -0,0 +1,924 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::de::value::Error;
+use serde::de::{self, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+use serde_yaml::{Mapping, Value};
+
+use crate::animation::{AnimationConfig, KeyframeConfig, TrackConfig};
+use crate::denoise::Denoiser;
+use crate::document::{Document, Location};
+use crate::film::ToneMapping;
+use crate::light::directional::DirectionalLight;
+use crate::light::point::PointLight;
+use crate::light::spot::SpotLight;
+use crate::model::Material;
+use crate::registry::{self, Registry};
+use crate::serialize::{CameraConfig, MaterialRanges, Shutter, WorldConfig, MAX_GENERATED};
+use crate::volume::heterogeneous::Emission;
+use crate::volume::homogeneous::HomogeneousVolume;
+use crate::world::{Fog, Sky};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a config, pointing at the value it is about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Path to the value, such as `entity[2].material.albedo`.
+    pub path: String,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, path.into(), message.into())
+    }
+
+    pub fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, path.into(), message.into())
+    }
+
+    fn new(severity: Severity, path: String, message: String) -> Self {
+        Self {
+            severity,
+            path,
+            message,
+            location: None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{location}: ")?;
+        }
+
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        match self.path.as_str() {
+            "" | "." => write!(f, "{severity}: {}", self.message),
+            path => write!(f, "{severity}: {path}: {}", self.message),
+        }
+    }
+}
+
+const GENERATORS: [&str; 4] = ["grid", "scatter", "circle", "spiral"];
+
+/// Checks a document for values that would fail to load or render implausibly, finding as
+/// many problems as possible in one go. Entries may use the custom types of `registry`.
+pub fn validate(document: &Document, registry: &Registry) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        registry,
+        diagnostics: Vec::new(),
+    };
+    validator.document(&document.value);
+
+    let mut diagnostics = validator.diagnostics;
+    for diagnostic in &mut diagnostics {
+        diagnostic.location = document.locate(&diagnostic.path).cloned();
+    }
+
+    diagnostics
+}
+
+struct Validator<'a> {
+    registry: &'a Registry,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn error(&mut self, path: &str, message: String) {
+        self.diagnostics.push(Diagnostic::error(path, message));
+    }
+
+    fn warning(&mut self, path: &str, message: String) {
+        self.diagnostics.push(Diagnostic::warning(path, message));
+    }
+
+    fn unknown_keys(&mut self, path: &str, mapping: &Mapping, known: &[&str]) {
+        for key in mapping.keys() {
+            match key.as_str() {
+                Some(key) if known.contains(&key) => {}
+                Some(key) => self.warning(&join(path, key), format!("unknown key `{key}`")),
+                None => self.warning(path, format!("unexpected key {key:?}")),
+            }
+        }
+    }
+
+    /// Warns about the keys of `value` that `T` does not read.
+    fn known_fields<'de, T: Deserialize<'de>>(&mut self, path: &str, value: &Value) {
+        if let Some(mapping) = value.as_mapping() {
+            self.unknown_keys(path, mapping, field_names::<T>());
+        }
+    }
+
+    /// Warns about the keys of an entry picked by its `type` that `T` does not read.
+    fn tagged_fields<'de, T: Deserialize<'de>>(&mut self, path: &str, mapping: &Mapping) {
+        let mut known = vec!["type"];
+        known.extend(field_names::<T>());
+        self.unknown_keys(path, mapping, &known);
+    }
+
+    fn document(&mut self, document: &Value) {
+        let Some(document) = document.as_mapping() else {
+            self.error(
+                "",
+                "expected a mapping of `camera`, `light`, `entity`, ...".into(),
+            );
+            return;
+        };
+
+        self.unknown_keys("", document, field_names::<WorldConfig>());
+
+        if let Some(camera) = document.get("camera") {
+            self.camera(camera);
+        }
+
+        for (index, light) in items(document, "light") {
+            self.light(&format!("light[{index}]"), light);
+        }
+
+        for (index, entity) in items(document, "entity") {
+            self.entity(&format!("entity[{index}]"), entity);
+        }
+
+        for (index, generator) in items(document, "generate") {
+            self.generator(&format!("generate[{index}]"), generator);
+        }
+
+        for (index, volume) in items(document, "volume") {
+            self.volume(&format!("volume[{index}]"), volume);
+        }
+
+        if let Some(sky) = document.get("sky") {
+            self.known_fields::<Sky>("sky", sky);
+
+            for key in ["from", "to"] {
+                if let Some(color) = sky.get(key) {
+                    self.color(&join("sky", key), color, false);
+                }
+            }
+        }
+
+        if let Some(fog) = document.get("fog") {
+            self.known_fields::<Fog>("fog", fog);
+
+            if let Some(color) = fog.get("color") {
+                self.color("fog.color", color, false);
+            }
+
+            if let Some(density) = number(Some(fog), "density") {
+                if density < 0.0 {
+                    self.warning("fog.density", "negative density disables the fog".into());
+                }
+            }
+        }
+
+        if let Some(tone_mapping) = document.get("tone_mapping") {
+            self.known_fields::<ToneMapping>("tone_mapping", tone_mapping);
+        }
+
+        if let Some(denoise) = document.get("denoise") {
+            self.known_fields::<Denoiser>("denoise", denoise);
+        }
+
+        if let Some(shutter) = document.get("shutter") {
+            self.known_fields::<Shutter>("shutter", shutter);
+        }
+
+        if let Some(animation) = document.get("animation") {
+            self.animation(animation);
+        }
+    }
+
+    /// Colors are untagged in the config, so serde can only say that nothing matched.
+    fn color(&mut self, path: &str, color: &Value, texture: bool) {
+        if let (true, Some(kind)) = (texture, registry::tag(color)) {
+            if !self.registry.is_texture(kind) {
+                self.error(
+                    &join(path, "type"),
+                    format!("unknown texture type `{kind}`"),
+                );
+            }
+            return;
+        }
+
+        let valid = match color {
+            Value::Number(hex) => hex.as_u64().is_some_and(|hex| hex <= 0xFFFFFF),
+            Value::Sequence(channels) => {
+                channels.len() == 3
+                    && channels
+                        .iter()
+                        .all(|channel| channel.as_u64().is_some_and(|channel| channel <= 255))
+            }
+            Value::Mapping(_) if color.get("linear").is_some() => color
+                .get("linear")
+                .and_then(Value::as_sequence)
+                .is_some_and(|channels| {
+                    channels.len() == 3 && channels.iter().all(Value::is_number)
+                }),
+            Value::Mapping(_) if texture => {
+                color.get("path").is_some_and(Value::is_string)
+                    && color.get("scale").is_some_and(Value::is_number)
+            }
+            _ => false,
+        };
+
+        if !valid {
+            let expected = if texture {
+                "a hex color such as 0xFF8000, [r, g, b] from 0 to 255, `linear: [r, g, b]`, or a texture with `scale` and `path`"
+            } else {
+                "a hex color such as 0xFF8000, [r, g, b] from 0 to 255 or `linear: [r, g, b]`"
+            };
+            self.error(path, format!("expected {expected}"));
+        }
+    }
+
+    fn camera(&mut self, camera: &Value) {
+        self.known_fields::<CameraConfig>("camera", camera);
+
+        for key in ["width", "height"] {
+            if let Some(size) = number(Some(camera), key) {
+                if size < 1.0 {
+                    self.error(
+                        &format!("camera.{key}"),
+                        format!("{key} must be at least 1"),
+                    );
+                }
+            }
+        }
+
+        if let Some(fov) = camera.get("fov").and_then(Value::as_f64) {
+            if fov <= 0.0 || fov >= 180.0 {
+                self.error(
+                    "camera.fov",
+                    format!("field of view {fov} must be between 0 and 180 degrees"),
+                );
+            }
+        }
+    }
+
+    fn light(&mut self, path: &str, light: &Value) {
+        let Some(fields) = light.as_mapping() else {
+            return;
+        };
+
+        match registry::tag(light) {
+            Some("directional") => self.tagged_fields::<DirectionalLight>(path, fields),
+            Some("point") => self.tagged_fields::<PointLight>(path, fields),
+            Some("spot") => self.tagged_fields::<SpotLight>(path, fields),
+            // Registered types are checked by their own deserializer.
+            Some(kind)
+                if self
+                    .registry
+                    .light_types()
+                    .iter()
+                    .any(|known| known == kind) =>
+            {
+                return
+            }
+            Some(other) => self.error(
+                &join(path, "type"),
+                format!(
+                    "unknown light type `{other}`, expected {}",
+                    alternatives(&self.registry.light_types())
+                ),
+            ),
+            None => self.error(path, "missing `type`".into()),
+        }
+
+        if let Some(color) = light.get("color") {
+            self.color(&join(path, "color"), color, false);
+        }
+
+        if let Some(intensity) = number(Some(light), "intensity") {
+            if intensity < 0.0 {
+                self.warning(
+                    &join(path, "intensity"),
+                    "negative intensity removes light from the scene".into(),
+                );
+            }
+        }
+
+        if let Some(direction) = vector(light.get("direction")) {
+            if length(direction) == 0.0 {
+                self.error(&join(path, "direction"), "direction has zero length".into());
+            }
+        }
+    }
+
+    fn entity(&mut self, path: &str, entity: &Value) {
+        let Some(fields) = entity.as_mapping() else {
+            return;
+        };
+
+        match registry::tag(entity) {
+            Some("sphere") => {
+                self.unknown_keys(
+                    path,
+                    fields,
+                    &["type", "material", "position", "radius", "velocity"],
+                );
+
+                if let Some(radius) = number(Some(entity), "radius") {
+                    if radius < 0.0 {
+                        self.warning(
+                            &join(path, "radius"),
+                            format!("negative radius {radius} renders as {}", -radius),
+                        );
+                    } else if radius == 0.0 {
+                        self.warning(&join(path, "radius"), "zero radius is invisible".into());
+                    }
+                }
+            }
+            Some("plane") => {
+                self.unknown_keys(
+                    path,
+                    fields,
+                    &["type", "material", "position", "normal", "velocity"],
+                );
+
+                if let Some(normal) = vector(entity.get("normal")) {
+                    if length(normal) == 0.0 {
+                        self.error(&join(path, "normal"), "normal has zero length".into());
+                    }
+                }
+            }
+            Some("mesh") => {
+                self.unknown_keys(
+                    path,
+                    fields,
+                    &["type", "material", "path", "position", "scale", "velocity"],
+                );
+
+                if number(Some(entity), "scale") == Some(0.0) {
+                    self.warning(&join(path, "scale"), "zero scale is invisible".into());
+                }
+            }
+            Some("gltf") => {
+                self.unknown_keys(
+                    path,
+                    fields,
+                    &["type", "path", "position", "scale", "velocity"],
+                );
+
+                if number(Some(entity), "scale") == Some(0.0) {
+                    self.warning(&join(path, "scale"), "zero scale is invisible".into());
+                }
+            }
+            Some(kind)
+                if self
+                    .registry
+                    .entity_types()
+                    .iter()
+                    .any(|known| known == kind) =>
+            {
+                return
+            }
+            Some(other) => self.error(
+                &join(path, "type"),
+                format!(
+                    "unknown entity type `{other}`, expected {}",
+                    alternatives(&self.registry.entity_types())
+                ),
+            ),
+            None => self.error(path, "missing `type`".into()),
+        }
+
+        if let Some(material) = entity.get("material") {
+            self.material(&join(path, "material"), material);
+        }
+    }
+
+    fn generator(&mut self, path: &str, generator: &Value) {
+        let Some(fields) = generator.as_mapping() else {
+            return;
+        };
+
+        let layout: &[&str] = match registry::tag(generator) {
+            Some("grid") => &["count", "spacing", "origin", "jitter"],
+            Some("scatter") => &["count", "min", "max", "spacing"],
+            Some("circle") => &["count", "center", "radius"],
+            Some("spiral") => &["count", "center", "radius", "turns"],
+            Some(other) => {
+                let kinds = GENERATORS.map(String::from);
+                self.error(
+                    &join(path, "type"),
+                    format!(
+                        "unknown generator type `{other}`, expected {}",
+                        alternatives(&kinds)
+                    ),
+                );
+                return;
+            }
+            None => {
+                self.error(path, "missing `type`".into());
+                return;
+            }
+        };
+
+        let mut known = vec!["type", "entity", "seed", "material"];
+        known.extend(layout);
+        self.unknown_keys(path, fields, &known);
+
+        let counts = match generator.get("count") {
+            Some(Value::Sequence(counts)) => counts.iter().filter_map(Value::as_u64).collect(),
+            Some(count) => count.as_u64().into_iter().collect(),
+            None => Vec::new(),
+        };
+        if counts.contains(&0) {
+            self.warning(
+                &join(path, "count"),
+                "a count of zero generates nothing".into(),
+            );
+        }
+
+        let total = counts
+            .iter()
+            .fold(1u64, |total, &count| total.saturating_mul(count));
+        if total > MAX_GENERATED {
+            self.error(
+                &join(path, "count"),
+                format!("{total} entities are more than the {MAX_GENERATED} a generator can add"),
+            );
+        }
+
+        if let Some(entity) = generator.get("entity") {
+            self.entity(&join(path, "entity"), entity);
+        }
+
+        if let Some(ranges) = generator.get("material") {
+            self.material_ranges(&join(path, "material"), ranges);
+        }
+    }
+
+    fn material_ranges(&mut self, path: &str, ranges: &Value) {
+        let Some(fields) = ranges.as_mapping() else {
+            return;
+        };
+
+        self.unknown_keys(path, fields, field_names::<MaterialRanges>());
+
+        match ranges.get("color") {
+            Some(Value::Sequence(colors)) if colors.len() == 2 => {
+                for (index, color) in colors.iter().enumerate() {
+                    self.color(&format!("{path}.color[{index}]"), color, false);
+                }
+            }
+            Some(_) => self.error(
+                &join(path, "color"),
+                "expected the two colors to pick between".into(),
+            ),
+            None => {}
+        }
+
+        for key in ["albedo", "reflectivity", "roughness"] {
+            let Some(range) = ranges.get(key) else {
+                continue;
+            };
+
+            let valid = range.as_sequence().is_some_and(|range| {
+                range.len() == 2 && range.iter().all(|bound| bound.as_f64().is_some())
+            });
+            if !valid {
+                self.error(&join(path, key), "expected `[min, max]`".into());
+            }
+        }
+    }
+
+    fn material(&mut self, path: &str, material: &Value) {
+        let Some(fields) = material.as_mapping() else {
+            return;
+        };
+
+        self.unknown_keys(path, fields, field_names::<Material>());
+
+        if let Some(color) = material.get("color") {
+            self.color(&join(path, "color"), color, true);
+        }
+
+        if let Some(albedo) = number(Some(material), "albedo") {
+            if albedo < 0.0 {
+                self.error(&join(path, "albedo"), "albedo cannot be negative".into());
+            } else if albedo > 1.0 {
+                self.warning(
+                    &join(path, "albedo"),
+                    format!("albedo {albedo} reflects more light than the surface receives"),
+                );
+            }
+        }
+
+        match material.get("surface") {
+            None | Some(Value::Null) => {}
+            Some(Value::Mapping(surface)) => {
+                let path = join(path, "surface");
+                self.unknown_keys(&path, surface, &["reflectivity", "roughness"]);
+
+                match number(material.get("surface"), "reflectivity") {
+                    Some(reflectivity) if !(0.0..=1.0).contains(&reflectivity) => self.warning(
+                        &join(&path, "reflectivity"),
+                        format!("reflectivity {reflectivity} is outside 0 to 1"),
+                    ),
+                    Some(_) => {}
+                    None => self.error(
+                        &path,
+                        "reflective surfaces need a numeric `reflectivity`; leave `surface` out for a diffuse one".into(),
+                    ),
+                }
+
+                if let Some(roughness) = number(material.get("surface"), "roughness") {
+                    if roughness < 0.0 {
+                        self.warning(
+                            &join(&path, "roughness"),
+                            format!("negative roughness behaves like {}", -roughness),
+                        );
+                    }
+                }
+            }
+            Some(_) => self.error(
+                &join(path, "surface"),
+                "expected a mapping with `reflectivity` and optional `roughness`".into(),
+            ),
+        }
+    }
+
+    fn volume(&mut self, path: &str, volume: &Value) {
+        let Some(fields) = volume.as_mapping() else {
+            return;
+        };
+
+        // Unknown types and shapes are reported by serde.
+        match registry::tag(volume) {
+            Some("homogeneous") => {
+                self.tagged_fields::<HomogeneousVolume>(path, fields);
+
+                if let Some(Value::Mapping(bounds)) = volume.get("bounds") {
+                    let known: &[&str] = match bounds.get("shape").and_then(Value::as_str) {
+                        Some("sphere") => &["shape", "center", "radius"],
+                        Some("box") => &["shape", "min", "max"],
+                        _ => &[],
+                    };
+                    if !known.is_empty() {
+                        self.unknown_keys(&join(path, "bounds"), bounds, known);
+                    }
+                }
+            }
+            Some("grid") => {
+                self.unknown_keys(
+                    path,
+                    fields,
+                    &[
+                        "type",
+                        "path",
+                        "min",
+                        "max",
+                        "density",
+                        "absorption",
+                        "scattering",
+                        "anisotropy",
+                        "emission",
+                        "samples",
+                    ],
+                );
+
+                if let Some(emission) = volume.get("emission") {
+                    self.known_fields::<Emission>(&join(path, "emission"), emission);
+                }
+            }
+            _ => {}
+        }
+
+        for key in ["absorption", "scattering", "density"] {
+            if let Some(value) = number(Some(volume), key) {
+                if value < 0.0 {
+                    self.error(&join(path, key), format!("{key} cannot be negative"));
+                }
+            }
+        }
+
+        if let Some(anisotropy) = number(Some(volume), "anisotropy") {
+            if anisotropy <= -1.0 || anisotropy >= 1.0 {
+                self.error(
+                    &join(path, "anisotropy"),
+                    format!("anisotropy {anisotropy} must be strictly between -1 and 1"),
+                );
+            }
+        }
+    }
+
+    fn animation(&mut self, animation: &Value) {
+        self.known_fields::<AnimationConfig>("animation", animation);
+
+        let tracks = animation.get("tracks").and_then(Value::as_sequence);
+        for (index, track) in tracks.into_iter().flatten().enumerate() {
+            let path = format!("animation.tracks[{index}]");
+            self.known_fields::<TrackConfig>(&path, track);
+
+            let keys = track.get("keys").and_then(Value::as_sequence);
+            for (index, key) in keys.into_iter().flatten().enumerate() {
+                self.known_fields::<KeyframeConfig>(&format!("{path}.keys[{index}]"), key);
+            }
+        }
+    }
+}
+
+fn items<'a>(document: &'a Mapping, key: &str) -> impl Iterator<Item = (usize, &'a Value)> {
+    document
+        .get(key)
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .enumerate()
+}
+
+fn number(value: Option<&Value>, key: &str) -> Option<f64> {
+    value?.get(key)?.as_f64()
+}
+
+/// Reads a vector written either as `[x, y, z]` or as `{ x, y, z }`.
+fn vector(value: Option<&Value>) -> Option<[f64; 3]> {
+    let value = value?;
+    let component = |index: usize, key: &str| match value {
+        Value::Sequence(items) => items.get(index)?.as_f64(),
+        Value::Mapping(_) => value.get(key)?.as_f64(),
+        _ => None,
+    };
+
+    Some([component(0, "x")?, component(1, "y")?, component(2, "z")?])
+}
+
+fn length([x, y, z]: [f64; 3]) -> f64 {
+    (x * x + y * y + z * z).sqrt()
+}
+
+/// Lists `kinds` as "`a`, `b` or `c`".
+fn alternatives(kinds: &[String]) -> String {
+    let quoted: Vec<_> = kinds.iter().map(|kind| format!("`{kind}`")).collect();
+
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+/// Keys that `T` reads from a mapping, taken from its `Deserialize` impl so that the lists
+/// checked here cannot drift from the config types.
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut names = FieldNames(&[]);
+    let _ = T::deserialize(&mut names);
+    names.0
+}
+
+/// Records the fields that a derived `Deserialize` impl asks for, and then gives up.
+struct FieldNames(&'static [&'static str]);
+
+impl<'de> Deserializer<'de> for &mut FieldNames {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("expected a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Error> {
+        self.0 = fields;
+        Err(de::Error::custom("only the field names were wanted"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::document::Document;
+    use crate::format::Format;
+    use crate::registry::Registry;
+
+    use super::{validate, Severity};
+
+    fn check(text: &str) -> Vec<(Severity, String, usize)> {
+        let document = Document::parse(text, Path::new("test.yaml"), Format::Yaml).unwrap();
+        validate(&document, &Registry::default())
+            .into_iter()
+            .map(|diagnostic| {
+                let line = diagnostic.location.map_or(0, |location| location.line);
+                (diagnostic.severity, diagnostic.path, line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_problem_with_its_location() {
+        let diagnostics = check(
+            "camera: { width: 100, height: 100 }
+light:
+  - type: point
+    position: [0, -5, 0]
+    color: 0xFFFFFF
+    intensty: 5
+entity:
+  - type: plane
+    position: [0, 1, 0]
+    normal: [0, 0, 0]
+    material: { albedo: 1.5 }
+  - type: sphere
+    position: [0, 0, -5]
+    radius: -1
+    material:
+      surface:
+        reflectivity: 0.5
+        roughnes: 0.2
+  - type: cube
+",
+        );
+
+        assert_eq!(
+            diagnostics,
+            [
+                (Severity::Warning, "light[0].intensty".to_string(), 6),
+                (Severity::Error, "entity[0].normal".to_string(), 10),
+                (
+                    Severity::Warning,
+                    "entity[0].material.albedo".to_string(),
+                    11
+                ),
+                (Severity::Warning, "entity[1].radius".to_string(), 14),
+                (
+                    Severity::Warning,
+                    "entity[1].material.surface.roughnes".to_string(),
+                    18
+                ),
+                (Severity::Error, "entity[2].type".to_string(), 19),
+            ]
+        );
+    }
+
+    #[test]
+    fn surface_without_reflectivity_is_an_error() {
+        let diagnostics = check(
+            "entity:
+  - type: sphere
+    material:
+      surface: { reflectivty: 0.5 }
+      color: grey
+",
+        );
+
+        assert!(diagnostics.contains(&(
+            Severity::Error,
+            "entity[0].material.color".to_string(),
+            5
+        )));
+
+        assert!(diagnostics.contains(&(
+            Severity::Error,
+            "entity[0].material.surface".to_string(),
+            4
+        )));
+    }
+
+    #[test]
+    fn generators_are_checked_like_entities() {
+        let diagnostics = check(
+            "camera: { width: 100, height: 100 }
+light: []
+entity: []
+generate:
+  - type: grid
+    count: [0, 4]
+    spacing: 1
+    entity: { type: sphere, position: [0, 0, 0], radius: -1, material: {} }
+    material: { albedo: 0.5, color: [0xFFFFFF] }
+  - type: hexagon
+    count: 3
+  - type: scatter
+    count: 4294967295
+    min: [0, 0, 0]
+    max: [1, 1, 1]
+    entity: { type: sphere, position: [0, 0, 0], radius: 1, material: {} }
+",
+        );
+
+        assert_eq!(
+            diagnostics,
+            [
+                (Severity::Warning, "generate[0].count".to_string(), 6),
+                (
+                    Severity::Warning,
+                    "generate[0].entity.radius".to_string(),
+                    8
+                ),
+                (Severity::Error, "generate[0].material.color".to_string(), 9),
+                (
+                    Severity::Error,
+                    "generate[0].material.albedo".to_string(),
+                    9
+                ),
+                (Severity::Error, "generate[1].type".to_string(), 10),
+                (Severity::Error, "generate[2].count".to_string(), 13),
+            ]
+        );
+    }
+
+    #[test]
+    fn typos_in_every_section_are_reported() {
+        let diagnostics = check(
+            "camera: { width: 100, height: 100, orgin: [0, 0, 0], fvo: 40 }
+light: []
+entity: []
+volume:
+  - type: homogeneous
+    bounds: { shape: sphere, center: [0, 0, 0], raduis: 1 }
+    absorption: 0.1
+    scattering: 0.1
+    anistropy: 0.5
+sky: { from: 0xFFFFFF, to: 0x000000, form: 0x101010 }
+fog: { color: 0xFFFFFF, densty: 0.1 }
+tone_mapping: { operater: aces }
+denoise: { enabled: true, iteration: 3 }
+shutter: { open: 0, clsoe: 1 }
+animation:
+  end: 10
+  tracks:
+    - target: camera.yaw
+      keys: [{ frame: 0, value: 0 }, { frame: 10, vaule: 90 }]
+",
+        );
+
+        let warnings: Vec<_> = diagnostics
+            .into_iter()
+            .map(|(severity, path, line)| {
+                assert_eq!(severity, Severity::Warning, "{path}");
+                (path, line)
+            })
+            .collect();
+
+        assert_eq!(
+            warnings,
+            [
+                ("camera.orgin".to_string(), 1),
+                ("camera.fvo".to_string(), 1),
+                ("volume[0].anistropy".to_string(), 9),
+                ("volume[0].bounds.raduis".to_string(), 6),
+                ("sky.form".to_string(), 10),
+                ("fog.densty".to_string(), 11),
+                ("tone_mapping.operater".to_string(), 12),
+                ("denoise.iteration".to_string(), 13),
+                ("shutter.clsoe".to_string(), 14),
+                ("animation.tracks[0].keys[1].vaule".to_string(), 19),
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_assets_have_no_diagnostics() {
+        for asset in [
+            "blackout",
+            "config",
+            "extreme-reflection",
+            "fog",
+            "library",
+            "meshes",
+            "smoke",
+            "spheres",
+            "turntable",
+        ] {
+            let path = format!("assets/{asset}.yaml");
+            let document = Document::open(Path::new(&path), Format::Yaml).unwrap();
+            assert_eq!(validate(&document, &Registry::default()), [], "{path}");
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pixels::{Error, Pixels, SurfaceTexture};
+use raytracer::{Camera, Framebuffer, Scene};
+use ultraviolet::{Mat3, Vec3};
+use winit::dpi::PhysicalSize;
+use winit::event::VirtualKeyCode;
+use winit::window::Window;
+use winit_input_helper::WinitInputHelper;
+
+const FORWARD: Vec3 = Vec3::new(0.0, 0.0, -1.0);
+const BACKWARD: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+const LEFT: Vec3 = Vec3::new(-1.0, 0.0, 0.0);
+const RIGHT: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+const TURN: f32 = 0.03;
+
+/// Moves the camera with the WASD keys relative to where it is facing and turns it with the
+/// arrow keys, returning whether it moved.
+pub fn steer(camera: &mut Camera, input: &WinitInputHelper) -> bool {
+    let before = (camera.origin, camera.yaw, camera.pitch);
+    let heading = Mat3::from_rotation_y(-camera.yaw);
+
+    if input.key_held(VirtualKeyCode::W) {
+        camera.origin += heading * FORWARD * camera.speed;
+    }
+
+    if input.key_held(VirtualKeyCode::S) {
+        camera.origin += heading * BACKWARD * camera.speed;
+    }
+
+    if input.key_held(VirtualKeyCode::A) {
+        camera.origin += heading * LEFT * camera.speed;
+    }
+
+    if input.key_held(VirtualKeyCode::D) {
+        camera.origin += heading * RIGHT * camera.speed;
+    }
+
+    if input.key_held(VirtualKeyCode::Left) {
+        camera.yaw -= TURN;
+    }
+
+    if input.key_held(VirtualKeyCode::Right) {
+        camera.yaw += TURN;
+    }
+
+    if input.key_held(VirtualKeyCode::Up) {
+        camera.pitch += TURN;
+    }
+
+    if input.key_held(VirtualKeyCode::Down) {
+        camera.pitch -= TURN;
+    }
+
+    (camera.origin, camera.yaw, camera.pitch) != before
+}
+
+pub fn window_size(scene: &Scene) -> PhysicalSize<u32> {
+    PhysicalSize::new(scene.width(), scene.height())
+}
+
+pub struct Renderer {
+    pixels: Pixels,
+    buffer: Framebuffer,
+    preview: Framebuffer,
+    budget: FrameBudget,
+}
+
+impl Renderer {
+    pub fn new(scene: &Scene, window: &Window, frame_time: Duration) -> anyhow::Result<Self> {
+        let surface_texture = SurfaceTexture::new(scene.width(), scene.height(), window);
+        let pixels = Pixels::new(scene.width(), scene.height(), surface_texture)
+            .context("create pixels renderer")?;
+        let buffer = Framebuffer::with_aovs(scene.width(), scene.height());
+        let budget = FrameBudget::new(frame_time);
+        Ok(Self {
+            pixels,
+            buffer,
+            preview: Framebuffer::default(),
+            budget,
+        })
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.buffer.samples()
+    }
+
+    /// How many times smaller than the window the last preview was rendered.
+    pub fn preview_scale(&self) -> u32 {
+        self.budget.scale
+    }
+
+    /// Throws away the accumulated samples, e.g. after the camera moved.
+    pub fn reset(&mut self) {
+        self.buffer.reset();
+    }
+
+    /// Adds a full resolution sample to the accumulated image.
+    pub fn render(&mut self, scene: &Scene) -> Result<(), Error> {
+        let start = Instant::now();
+        scene.render(&mut self.buffer);
+
+        if self.buffer.samples() == 1 {
+            self.budget.record(start.elapsed(), 1);
+        }
+
+        Self::present(scene, &self.buffer, self.pixels.frame_mut());
+        self.pixels.render()
+    }
+
+    /// Renders a single sample at whatever resolution fits the frame budget, for use
+    /// while the camera is moving.
+    pub fn render_preview(&mut self, scene: &Scene) -> Result<(), Error> {
+        let scale = self.budget.scale;
+        let width = (self.buffer.width() / scale).max(1);
+        let height = (self.buffer.height() / scale).max(1);
+
+        if self.preview.width() != width || self.preview.height() != height {
+            self.preview = Framebuffer::with_aovs(width, height);
+        }
+
+        let start = Instant::now();
+        self.preview.reset();
+        scene.render(&mut self.preview);
+        self.budget.record(start.elapsed(), scale);
+
+        let upscaled = self
+            .preview
+            .upscale(self.buffer.width(), self.buffer.height());
+        Self::present(scene, &upscaled, self.pixels.frame_mut());
+        self.pixels.render()
+    }
+
+    fn present(scene: &Scene, buffer: &Framebuffer, frame: &mut [u8]) {
+        if scene.denoiser.enabled {
+            let denoised = scene.denoiser.apply(buffer);
+            denoised.write_rgba(&scene.tone_mapping, frame);
+        } else {
+            buffer.write_rgba(&scene.tone_mapping, frame);
+        }
+    }
+
+    pub fn resize(&mut self, size: impl Into<PhysicalSize<u32>>) {
+        let size = size.into();
+
+        self.pixels
+            .resize_surface(size.width, size.height)
+            .expect("resize window");
+        self.pixels
+            .resize_buffer(size.width, size.height)
+            .expect("resize buffer");
+        self.buffer.resize(size.width, size.height);
+    }
+}
+
+/// Picks the preview resolution that keeps interactive frames within a time budget.
+#[derive(Debug)]
+struct FrameBudget {
+    target: Duration,
+    scale: u32,
+}
+
+impl FrameBudget {
+    const SCALES: [u32; 4] = [1, 2, 4, 8];
+
+    fn new(target: Duration) -> FrameBudget {
+        FrameBudget { target, scale: 1 }
+    }
+
+    /// Render time is proportional to the number of pixels, so a frame rendered at `scale`
+    /// predicts the cost of every other scale.
+    fn record(&mut self, elapsed: Duration, scale: u32) {
+        let full_resolution = elapsed.as_secs_f64() * (scale * scale) as f64;
+        let target = self.target.as_secs_f64();
+
+        self.scale = FrameBudget::SCALES
+            .into_iter()
+            .find(|&scale| full_resolution / (scale * scale) as f64 <= target)
+            .unwrap_or(FrameBudget::SCALES[FrameBudget::SCALES.len() - 1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FrameBudget;
+
+    #[test]
+    fn frame_budget_picks_smallest_scale_that_fits() {
+        let mut budget = FrameBudget::new(Duration::from_millis(16));
+
+        budget.record(Duration::from_millis(10), 1);
+        assert_eq!(budget.scale, 1);
+
+        budget.record(Duration::from_millis(40), 1);
+        assert_eq!(budget.scale, 2);
+
+        budget.record(Duration::from_millis(30), 2);
+        assert_eq!(budget.scale, 4);
+
+        budget.record(Duration::from_millis(2), 4);
+        assert_eq!(budget.scale, 2);
+
+        budget.record(Duration::from_micros(500), 4);
+        assert_eq!(budget.scale, 1);
+
+        budget.record(Duration::from_secs(10), 1);
+        assert_eq!(budget.scale, 8);
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use ultraviolet::Vec3;
+
+/// A voxel grid in the Mitsuba `.vol` format: a small header with the resolution, channel
+/// count and world-space bounding box followed by the voxels in x-fastest order.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    resolution: [usize; 3],
+    channels: usize,
+    data: Vec<f32>,
+    pub min: Vec3,
+    pub max: Vec3,
+    /// The file this grid was loaded from, if any.
+    pub path: Option<PathBuf>,
+}
+
+impl Grid {
+    const MAGIC: &'static [u8] = b"VOL";
+    const VERSION: u8 = 3;
+    const FLOAT32: i32 = 1;
+    const UINT8: i32 = 3;
+
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Grid> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        let grid =
+            Grid::parse(&bytes).with_context(|| format!("parse voxel grid {}", path.display()))?;
+
+        Ok(Grid {
+            path: Some(path.to_path_buf()),
+            ..grid
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Grid> {
+        let mut reader = Reader(bytes);
+
+        ensure!(reader.take(3)? == Grid::MAGIC, "missing VOL header");
+        ensure!(reader.take(1)?[0] == Grid::VERSION, "unsupported version");
+
+        let encoding = reader.i32()?;
+        let resolution = [
+            reader.dimension()?,
+            reader.dimension()?,
+            reader.dimension()?,
+        ];
+        let channels = reader.dimension()?;
+        let min = Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?);
+        let max = Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?);
+
+        let size = match encoding {
+            Grid::FLOAT32 => 4,
+            Grid::UINT8 => 1,
+            _ => bail!("unsupported encoding {encoding}"),
+        };
+
+        let count = resolution
+            .iter()
+            .chain([&channels])
+            .try_fold(1usize, |count, &n| count.checked_mul(n))
+            .context("grid is too large")?;
+        ensure!(
+            count
+                .checked_mul(size)
+                .is_some_and(|len| len <= reader.0.len()),
+            "unexpected end of file"
+        );
+
+        let data = match encoding {
+            Grid::FLOAT32 => (0..count).map(|_| reader.f32()).collect::<Result<_, _>>()?,
+            _ => reader
+                .take(count)?
+                .iter()
+                .map(|&value| value as f32 / 255.0)
+                .collect(),
+        };
+
+        Ok(Grid {
+            resolution,
+            channels,
+            data,
+            min,
+            max,
+            path: None,
+        })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Largest value stored in `channel`.
+    pub fn max_value(&self, channel: usize) -> f32 {
+        self.data
+            .iter()
+            .skip(channel)
+            .step_by(self.channels)
+            .copied()
+            .fold(0.0, f32::max)
+    }
+
+    /// Trilinearly interpolated value of `channel` at `point`, or zero outside the grid.
+    pub fn sample(&self, point: Vec3, channel: usize) -> f32 {
+        let extent = self.max - self.min;
+        let local = (point - self.min) / extent;
+
+        if local.component_min() < 0.0 || local.component_max() > 1.0 {
+            return 0.0;
+        }
+
+        let [nx, ny, nz] = self.resolution;
+        let x = local.x * nx as f32 - 0.5;
+        let y = local.y * ny as f32 - 0.5;
+        let z = local.z * nz as f32 - 0.5;
+
+        let (x0, fx) = split(x, nx);
+        let (y0, fy) = split(y, ny);
+        let (z0, fz) = split(z, nz);
+        let x1 = (x0 + 1).min(nx - 1);
+        let y1 = (y0 + 1).min(ny - 1);
+        let z1 = (z0 + 1).min(nz - 1);
+
+        let at = |x: usize, y: usize, z: usize| {
+            self.data[((z * ny + y) * nx + x) * self.channels + channel]
+        };
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let c00 = lerp(at(x0, y0, z0), at(x1, y0, z0), fx);
+        let c10 = lerp(at(x0, y1, z0), at(x1, y1, z0), fx);
+        let c01 = lerp(at(x0, y0, z1), at(x1, y0, z1), fx);
+        let c11 = lerp(at(x0, y1, z1), at(x1, y1, z1), fx);
+
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+}
+
+/// Splits a continuous voxel coordinate into the lower voxel index and the blend factor.
+fn split(coord: f32, size: usize) -> (usize, f32) {
+    let clamped = coord.clamp(0.0, (size - 1) as f32);
+    let index = clamped.floor();
+    (index as usize, clamped - index)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(self.0.len() >= count, "unexpected end of file");
+        let (head, tail) = self.0.split_at(count);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn dimension(&mut self) -> anyhow::Result<usize> {
+        let value = self.i32()?;
+        ensure!(value > 0, "invalid dimension {value}");
+        Ok(value as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use super::Grid;
+
+    fn encode(resolution: [i32; 3], channels: i32, data: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        bytes.extend(1i32.to_le_bytes());
+        for value in resolution.into_iter().chain([channels]) {
+            bytes.extend(value.to_le_bytes());
+        }
+        for value in [0.0f32, 0.0, 0.0, 2.0, 1.0, 1.0].iter().chain(data) {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_header_and_interpolates() {
+        let grid = Grid::parse(&encode([2, 1, 1], 2, &[0.0, 5.0, 1.0, 7.0])).unwrap();
+
+        assert_eq!(grid.channels(), 2);
+        assert_eq!(grid.max, Vec3::new(2.0, 1.0, 1.0));
+        assert_eq!(grid.max_value(0), 1.0);
+        assert_eq!(grid.max_value(1), 7.0);
+
+        assert_eq!(grid.sample(Vec3::new(0.5, 0.5, 0.5), 0), 0.0);
+        assert_eq!(grid.sample(Vec3::new(1.0, 0.5, 0.5), 0), 0.5);
+        assert_eq!(grid.sample(Vec3::new(1.5, 0.5, 0.5), 1), 7.0);
+        assert_eq!(grid.sample(Vec3::new(3.0, 0.5, 0.5), 1), 0.0);
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut bytes = encode([2, 2, 2], 1, &[1.0; 8]);
+        bytes.truncate(bytes.len() - 4);
+        assert!(Grid::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_sizes_larger_than_the_file() {
+        let huge = encode([i32::MAX, i32::MAX, i32::MAX], i32::MAX, &[]);
+        assert!(Grid::parse(&huge).is_err());
+
+        let large = encode([1 << 16, 1 << 16, 1], 1, &[1.0]);
+        assert!(Grid::parse(&large).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::ops::{Add, Range};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use ultraviolet::Vec3;
+
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::registry;
+use crate::sampler;
+use crate::volume::grid::Grid;
+use crate::volume::{henyey_greenstein, Bounds, Volume};
+use crate::world::World;
+
+/// Light emitted by the medium, scaled by one of the grid's channels.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Emission {
+    #[serde(default = "Emission::channel")]
+    pub channel: usize,
+    pub color: Color,
+    pub intensity: f32,
+}
+
+/// A medium whose density varies according to a voxel grid. Transmittance is estimated with
+/// ratio tracking and in-scattering with delta tracking, so every call returns a noisy but
+/// unbiased estimate.
+#[derive(Debug, Clone)]
+pub struct HeterogeneousVolume {
+    pub grid: Grid,
+    pub density: f32,
+    pub absorption: f32,
+    pub scattering: f32,
+    pub anisotropy: f32,
+    pub emission: Option<Emission>,
+    pub samples: usize,
+    majorant: f32,
+}
+
+impl Emission {
+    fn channel() -> usize {
+        1
+    }
+}
+
+impl HeterogeneousVolume {
+    const DENSITY_CHANNEL: usize = 0;
+
+    pub fn new(
+        grid: Grid,
+        density: f32,
+        absorption: f32,
+        scattering: f32,
+        anisotropy: f32,
+        emission: Option<Emission>,
+        samples: usize,
+    ) -> HeterogeneousVolume {
+        let max_density = grid.max_value(Self::DENSITY_CHANNEL) * density;
+        let majorant = max_density * (absorption + scattering);
+
+        HeterogeneousVolume {
+            grid,
+            density,
+            absorption,
+            scattering,
+            anisotropy,
+            emission,
+            samples,
+            majorant,
+        }
+    }
+
+    fn density_at(&self, point: Vec3) -> f32 {
+        self.grid.sample(point, Self::DENSITY_CHANNEL) * self.density
+    }
+
+    /// Samples the distance to the next tentative collision against the majorant.
+    fn step(&self) -> f32 {
+        -(1.0 - sampler::next_f32()).ln() / self.majorant
+    }
+
+    /// Delta tracking: returns the first real collision along `span`, if any.
+    fn collide(&self, ray: &Ray, span: Range<f32>) -> Option<Vec3> {
+        let mut t = span.start;
+
+        loop {
+            t += self.step();
+
+            if t >= span.end {
+                return None;
+            }
+
+            let point = ray.at(t);
+            let extinction = self.density_at(point) * (self.absorption + self.scattering);
+
+            if sampler::next_f32() < extinction / self.majorant {
+                return Some(point);
+            }
+        }
+    }
+
+    fn in_scattered(&self, world: &World, ray: &Ray, point: Vec3) -> Color {
+        world
+            .lights
+            .iter()
+            .map(|light| {
+                let incident = light.illuminate(point);
+                let shadow_ray = Ray::new(point, incident.direction).with_time(ray.time);
+                let visibility = world.visibility(&shadow_ray, 0.0..incident.distance);
+                let cos_theta = incident.direction.dot(ray.direction);
+                let phase = henyey_greenstein(self.anisotropy, cos_theta);
+                incident.radiance * (visibility * phase)
+            })
+            .fold(Color::default(), Color::add)
+    }
+
+    fn emitted(&self, point: Vec3) -> Color {
+        match &self.emission {
+            Some(emission) => {
+                let strength = self.grid.sample(point, emission.channel) * emission.intensity;
+                emission.color * strength
+            }
+            None => Color::default(),
+        }
+    }
+}
+
+impl Volume for HeterogeneousVolume {
+    fn span(&self, ray: &Ray, range: Range<f32>) -> Option<Range<f32>> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+
+        let bounds = Bounds::Box {
+            min: self.grid.min,
+            max: self.grid.max,
+        };
+
+        bounds.clip(ray, range)
+    }
+
+    fn transmittance(&self, ray: &Ray, span: Range<f32>) -> f32 {
+        let mut transmittance = 1.0;
+        let mut t = span.start;
+
+        loop {
+            t += self.step();
+
+            if t >= span.end {
+                return transmittance;
+            }
+
+            let extinction = self.density_at(ray.at(t)) * (self.absorption + self.scattering);
+            transmittance *= 1.0 - extinction / self.majorant;
+        }
+    }
+
+    fn scattering(&self, world: &World, ray: &Ray, span: Range<f32>) -> Color {
+        let samples = self.samples.max(1);
+        let extinction = self.absorption + self.scattering;
+
+        let total = (0..samples)
+            .filter_map(|_| self.collide(ray, span.clone()))
+            .map(|point| {
+                let scattered =
+                    self.in_scattered(world, ray, point) * (self.scattering / extinction);
+                let emitted = self.emitted(point) * (self.absorption / extinction);
+                scattered + emitted
+            })
+            .fold(Color::default(), Color::add);
+
+        total * (1.0 / samples as f32)
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        #[derive(Serialize)]
+        struct Entry<'a> {
+            path: &'a Path,
+            min: Vec3,
+            max: Vec3,
+            density: f32,
+            absorption: f32,
+            scattering: f32,
+            anisotropy: f32,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            emission: &'a Option<Emission>,
+            samples: usize,
+        }
+
+        let entry = Entry {
+            path: self.grid.path.as_deref()?,
+            min: self.grid.min,
+            max: self.grid.max,
+            density: self.density,
+            absorption: self.absorption,
+            scattering: self.scattering,
+            anisotropy: self.anisotropy,
+            emission: &self.emission,
+            samples: self.samples,
+        };
+        registry::tagged("grid", &entry)
+    }
+}
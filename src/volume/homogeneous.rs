@@ -0,0 +1,77 @@
+use std::ops::{Add, Range};
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::registry;
+use crate::sampler;
+use crate::volume::{henyey_greenstein, Bounds, Volume};
+use crate::world::World;
+
+/// A medium with constant density inside its bounds.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HomogeneousVolume {
+    pub bounds: Bounds,
+    pub absorption: f32,
+    pub scattering: f32,
+    /// Henyey–Greenstein asymmetry, from `-1.0` (back scattering) to `1.0` (forward scattering).
+    #[serde(default)]
+    pub anisotropy: f32,
+    #[serde(default = "HomogeneousVolume::steps")]
+    pub steps: usize,
+}
+
+impl HomogeneousVolume {
+    fn extinction(&self) -> f32 {
+        self.absorption + self.scattering
+    }
+
+    fn steps() -> usize {
+        32
+    }
+}
+
+impl Volume for HomogeneousVolume {
+    fn span(&self, ray: &Ray, range: Range<f32>) -> Option<Range<f32>> {
+        self.bounds.clip(ray, range)
+    }
+
+    fn transmittance(&self, _ray: &Ray, span: Range<f32>) -> f32 {
+        (-self.extinction() * (span.end - span.start)).exp()
+    }
+
+    fn scattering(&self, world: &World, ray: &Ray, span: Range<f32>) -> Color {
+        let steps = self.steps.max(1);
+        let step = (span.end - span.start) / steps as f32;
+        let jitter = sampler::next_f32();
+
+        (0..steps)
+            .map(|index| {
+                let t = span.start + (index as f32 + jitter) * step;
+                let point = ray.at(t);
+                let attenuation = (-self.extinction() * (t - span.start)).exp();
+
+                let in_scattered = world
+                    .lights
+                    .iter()
+                    .map(|light| {
+                        let incident = light.illuminate(point);
+                        let shadow_ray = Ray::new(point, incident.direction).with_time(ray.time);
+                        let visibility = world.visibility(&shadow_ray, 0.0..incident.distance);
+                        let cos_theta = incident.direction.dot(ray.direction);
+                        let phase = henyey_greenstein(self.anisotropy, cos_theta);
+                        incident.radiance * (visibility * phase)
+                    })
+                    .fold(Color::default(), Color::add);
+
+                in_scattered * (attenuation * self.scattering * step)
+            })
+            .fold(Color::default(), Color::add)
+    }
+
+    fn to_config(&self) -> Option<Value> {
+        registry::tagged("homogeneous", self)
+    }
+}
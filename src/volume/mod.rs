@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use ultraviolet::Vec3;
+
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::world::World;
+
+pub mod grid;
+pub mod heterogeneous;
+pub mod homogeneous;
+
+/// A participating medium that attenuates and scatters light passing through it.
+pub trait Volume {
+    /// Part of `range` along `ray` that lies inside the volume.
+    fn span(&self, ray: &Ray, range: Range<f32>) -> Option<Range<f32>>;
+
+    /// Fraction of light that makes it through `span` without being absorbed or scattered.
+    fn transmittance(&self, ray: &Ray, span: Range<f32>) -> f32;
+
+    /// Light from the world's light sources scattered back along `ray` within `span`.
+    fn scattering(&self, world: &World, ray: &Ray, span: Range<f32>) -> Color;
+
+    /// The config entry that loads this volume, used when saving scenes. Volumes that cannot
+    /// be written to a config return `None`.
+    fn to_config(&self) -> Option<Value> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "shape")]
+#[serde(rename_all = "snake_case")]
+pub enum Bounds {
+    Sphere { center: Vec3, radius: f32 },
+    Box { min: Vec3, max: Vec3 },
+}
+
+impl Bounds {
+    pub fn clip(&self, ray: &Ray, range: Range<f32>) -> Option<Range<f32>> {
+        let (near, far) = match *self {
+            Bounds::Sphere { center, radius } => {
+                let local = center - ray.origin;
+                let projected = local.dot(ray.direction);
+                let distance = local.mag_sq() - projected * projected;
+                let radius_sq = radius * radius;
+
+                if distance > radius_sq {
+                    return None;
+                }
+
+                let thickness = (radius_sq - distance).sqrt();
+                (projected - thickness, projected + thickness)
+            }
+
+            Bounds::Box { min, max } => {
+                let inverse = Vec3::one() / ray.direction;
+                let a = (min - ray.origin) * inverse;
+                let b = (max - ray.origin) * inverse;
+
+                let near = a.min_by_component(b).component_max();
+                let far = a.max_by_component(b).component_min();
+                (near, far)
+            }
+        };
+
+        let start = near.max(range.start);
+        let end = far.min(range.end);
+
+        (start < end).then_some(start..end)
+    }
+}
+
+/// Henyey–Greenstein phase function, where `cos_theta` is the angle between the
+/// direction light travels and the direction it is scattered to.
+pub fn henyey_greenstein(g: f32, cos_theta: f32) -> f32 {
+    let g_sq = g * g;
+    let denom = 1.0 + g_sq - 2.0 * g * cos_theta;
+    (1.0 - g_sq) / (4.0 * std::f32::consts::PI * denom * denom.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::ray::Ray;
+
+    use super::{henyey_greenstein, Bounds};
+
+    #[test]
+    fn box_clip_is_limited_to_range() {
+        let bounds = Bounds::Box {
+            min: Vec3::new(-1.0, -1.0, -6.0),
+            max: Vec3::new(1.0, 1.0, -4.0),
+        };
+        let ray = Ray::new(Vec3::zero(), -Vec3::unit_z());
+
+        assert_eq!(bounds.clip(&ray, Ray::UNBOUNDED), Some(4.0..6.0));
+        assert_eq!(bounds.clip(&ray, 0.0..5.0), Some(4.0..5.0));
+        assert_eq!(bounds.clip(&ray, 0.0..3.0), None);
+    }
+
+    #[test]
+    fn sphere_clip_starts_at_origin_when_inside() {
+        let bounds = Bounds::Sphere {
+            center: Vec3::zero(),
+            radius: 2.0,
+        };
+        let ray = Ray::new(Vec3::zero(), Vec3::unit_x());
+
+        assert_eq!(bounds.clip(&ray, Ray::UNBOUNDED), Some(0.0..2.0));
+    }
+
+    #[test]
+    fn isotropic_phase_is_uniform() {
+        let expected = 1.0 / (4.0 * std::f32::consts::PI);
+        assert!((henyey_greenstein(0.0, 1.0) - expected).abs() < 1e-6);
+        assert!((henyey_greenstein(0.0, -1.0) - expected).abs() < 1e-6);
+        assert!(henyey_greenstein(0.5, 1.0) > henyey_greenstein(0.5, -1.0));
+    }
+}
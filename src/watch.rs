@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Notices changes to a set of files by polling their modification times.
+pub struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>, interval: Duration) -> Self {
+        let mut watcher = Self {
+            files: Vec::new(),
+            interval,
+            last_poll: Instant::now(),
+        };
+        watcher.watch(paths);
+        watcher
+    }
+
+    /// Replaces the watched files, treating their current state as unchanged.
+    pub fn watch(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = Self::modified(&path);
+                (path, modified)
+            })
+            .collect();
+    }
+
+    /// Returns whether any file was modified, created or removed since the last poll. Polls at
+    /// most once per interval and reports `false` in between.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < self.interval {
+            return false;
+        }
+
+        self.last_poll = Instant::now();
+
+        let mut changed = false;
+        for (path, modified) in &mut self.files {
+            let current = Self::modified(path);
+
+            if current != *modified {
+                *modified = current;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::Watcher;
+
+    #[test]
+    fn reports_each_change_once() {
+        let path = std::env::temp_dir().join(format!("watch-test-{}", std::process::id()));
+        std::fs::write(&path, "a").unwrap();
+
+        let mut watcher = Watcher::new([path.clone()], Duration::ZERO);
+        assert!(!watcher.changed());
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+}
@@ -1,24 +1,52 @@
-use std::ops::Add;
+use std::ops::{Add, Range};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use ultraviolet::Vec3;
 
+use crate::aov::AovSample;
 use crate::color::Color;
 use crate::light::Light;
-use crate::model::{Material, SurfaceType, Texture};
+use crate::model::mesh::{Mesh, MeshData};
+use crate::model::plane::Plane;
+use crate::model::sphere::Sphere;
+use crate::model::{Material, MaterialIds, SurfaceType, Texture};
 use crate::ray::{Intersectable, Intersection, Ray};
+use crate::stats;
+use crate::volume::Volume;
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct Sky {
     pub from: Color,
     pub to: Color,
 }
 
+/// Exponential fog applied to everything seen through the camera.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Fog {
+    pub color: Color,
+    pub density: f32,
+}
+
+impl Fog {
+    pub fn apply(&self, color: Color, distance: f32) -> Color {
+        if self.density <= 0.0 {
+            return color;
+        }
+
+        let transmittance = (-self.density * distance).exp();
+        Color::lerp(self.color, color, transmittance)
+    }
+}
+
 #[derive(Default)]
 pub struct World {
     pub lights: Vec<Box<dyn Light>>,
     pub entities: Vec<Box<dyn Entity>>,
+    pub volumes: Vec<Box<dyn Volume>>,
+    pub fog: Option<Fog>,
     pub reflection_depth: usize,
+    pub reflection_samples: usize,
     pub sky: Sky,
 }
 
@@ -27,16 +55,91 @@ impl World {
         World::default()
     }
 
-    fn cast_ray(&self, ray: Ray, depth: usize) -> Color {
+    pub fn builder() -> WorldBuilder {
+        WorldBuilder {
+            world: World {
+                reflection_depth: 5,
+                reflection_samples: 8,
+                ..World::default()
+            },
+            material_ids: MaterialIds::default(),
+        }
+    }
+
+    pub fn cast_ray(&self, ray: Ray, depth: usize) -> Color {
         if depth > self.reflection_depth {
             return Color::default();
         }
 
-        if let Some(intersection) = self.intersect(&ray) {
+        let intersection = self.intersect(&ray, Ray::UNBOUNDED);
+        let distance = intersection.as_ref().map_or(f32::INFINITY, |hit| hit.t);
+
+        let color = if let Some(intersection) = intersection {
             self.compute_light(intersection, depth)
         } else {
             self.sky_color(&ray)
+        };
+
+        self.through_media(&ray, distance, color)
+    }
+
+    /// Traces a camera ray, also recording what it hit for the AOV buffers.
+    pub fn cast_primary(&self, ray: Ray) -> (Color, AovSample) {
+        stats::count(|stats| stats.primary_rays += 1);
+
+        let Some(intersection) = self.intersect(&ray, Ray::UNBOUNDED) else {
+            let sky = self.sky_color(&ray);
+            let color = self.through_media(&ray, f32::INFINITY, sky);
+            return (color, AovSample::miss(sky, color));
+        };
+
+        let shading = self.shade(&intersection, 0);
+        let color = self.through_media(&ray, intersection.t, shading.total());
+        let aov = AovSample::hit(&intersection, &shading, color);
+
+        (color, aov)
+    }
+
+    /// Applies the global fog to `color` seen at `distance`, then composites the volumes
+    /// in front of it back to front.
+    fn through_media(&self, ray: &Ray, distance: f32, color: Color) -> Color {
+        let mut color = match &self.fog {
+            Some(fog) => fog.apply(color, distance),
+            None => color,
+        };
+
+        let mut spans: Vec<_> = self
+            .volumes
+            .iter()
+            .filter_map(|volume| Some((volume, volume.span(ray, 0.0..distance)?)))
+            .collect();
+
+        spans.sort_by(|(_, a), (_, b)| b.start.total_cmp(&a.start));
+
+        for (volume, span) in spans {
+            let transmittance = volume.transmittance(ray, span.clone());
+            color = color * transmittance + volume.scattering(self, ray, span);
+        }
+
+        color
+    }
+
+    /// Fraction of light that travels along `ray` within `range` without being blocked
+    /// by an entity or absorbed by a volume.
+    pub fn visibility(&self, ray: &Ray, range: Range<f32>) -> f32 {
+        stats::count(|stats| stats.shadow_rays += 1);
+
+        if self.intersect(ray, range.clone()).is_some() {
+            return 0.0;
         }
+
+        self.volumes
+            .iter()
+            .filter_map(|volume| {
+                let span = volume.span(ray, range.clone())?;
+                Some(volume.transmittance(ray, span))
+            })
+            .product()
     }
 
     pub fn sky_color(&self, ray: &Ray) -> Color {
@@ -45,36 +148,311 @@ impl World {
     }
 
     pub fn compute_light(&self, intersection: Intersection, depth: usize) -> Color {
+        self.shade(&intersection, depth).total()
+    }
+
+    fn shade(&self, intersection: &Intersection, depth: usize) -> Shading {
         match intersection.contact.material.surface {
-            SurfaceType::Reflective { reflectivity } => {
-                let color = self.diffuse_color(&intersection);
-                let reflection_ray = Ray::reflect(&intersection);
-                let reflected_color = self.cast_ray(reflection_ray, depth + 1) * reflectivity;
-                color * (1.0 - reflectivity) + reflected_color
-            }
-
-            SurfaceType::Diffuse => self.diffuse_color(&intersection),
+            SurfaceType::Reflective {
+                reflectivity,
+                roughness,
+            } => Shading {
+                direct: self.diffuse_color(intersection) * (1.0 - reflectivity),
+                reflection: self.reflected_color(intersection, roughness, depth) * reflectivity,
+            },
+
+            SurfaceType::Diffuse => Shading {
+                direct: self.diffuse_color(intersection),
+                reflection: Color::default(),
+            },
+        }
+    }
+
+    /// Rough surfaces average several perturbed reflections, but only at the first bounce
+    /// so the number of rays doesn't grow exponentially with `reflection_depth`.
+    fn reflected_color(&self, intersection: &Intersection, roughness: f32, depth: usize) -> Color {
+        if roughness <= 0.0 {
+            stats::count(|stats| stats.reflection_rays += 1);
+            return self.cast_ray(Ray::reflect(intersection), depth + 1);
         }
+
+        let samples = if depth == 0 {
+            self.reflection_samples.max(1)
+        } else {
+            1
+        };
+        stats::count(|stats| stats.reflection_rays += samples as u64);
+
+        let total = (0..samples)
+            .map(|_| self.cast_ray(Ray::glossy(intersection, roughness), depth + 1))
+            .fold(Color::default(), Color::add);
+
+        total * (1.0 / samples as f32)
     }
 
     fn diffuse_color(&self, intersection: &Intersection) -> Color {
         self.lights
             .iter()
-            .map(|light| light.shading(&self, &intersection))
+            .map(|light| light.shading(self, intersection))
             .fold(Color::default(), Color::add)
     }
 }
 
+/// Light leaving a surface, split by how it got there.
+pub struct Shading {
+    /// Light from the light sources bouncing straight off the surface.
+    pub direct: Color,
+    /// Light arriving from the mirror direction.
+    pub reflection: Color,
+}
+
+impl Shading {
+    pub fn total(&self) -> Color {
+        self.direct + self.reflection
+    }
+}
+
 impl Intersectable for World {
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+    fn intersect(&self, ray: &Ray, range: Range<f32>) -> Option<Intersection<'_>> {
+        stats::count(|stats| stats.intersection_tests += self.entities.len() as u64);
+
         self.entities
             .iter()
-            .flat_map(|entity| entity.intersect(ray))
+            .enumerate()
+            .flat_map(|(index, entity)| {
+                let mut intersection = entity.intersect(ray, range.clone())?;
+                intersection.entity = index;
+                Some(intersection)
+            })
             .min_by(Intersection::closest)
     }
 }
 
+/// Assembles a [`World`] in code, starting from the same defaults as a config file.
+pub struct WorldBuilder {
+    world: World,
+    material_ids: MaterialIds,
+}
+
+impl WorldBuilder {
+    pub fn sphere(mut self, center: Vec3, radius: f32, material: Material) -> Self {
+        let material = self.next_material(material);
+        self.entity(Sphere {
+            center,
+            radius,
+            material,
+            ..Sphere::default()
+        })
+    }
+
+    pub fn plane(mut self, position: Vec3, normal: Vec3, material: Material) -> Self {
+        let material = self.next_material(material);
+        self.entity(Plane {
+            position,
+            normal: normal.normalized(),
+            material,
+            ..Plane::default()
+        })
+    }
+
+    /// Fails if `data` does not pass [`MeshData::validate`].
+    pub fn mesh(mut self, data: MeshData, material: Material) -> anyhow::Result<Self> {
+        let material = self.next_material(material);
+        Ok(self.entity(Mesh::new(data, material)?))
+    }
+
+    pub fn entity(mut self, entity: impl Entity + 'static) -> Self {
+        self.world.entities.push(Box::new(entity));
+        self
+    }
+
+    pub fn light(mut self, light: impl Light + 'static) -> Self {
+        self.world.lights.push(Box::new(light));
+        self
+    }
+
+    pub fn volume(mut self, volume: impl Volume + 'static) -> Self {
+        self.world.volumes.push(Box::new(volume));
+        self
+    }
+
+    pub fn sky(mut self, sky: Sky) -> Self {
+        self.world.sky = sky;
+        self
+    }
+
+    pub fn fog(mut self, fog: Fog) -> Self {
+        self.world.fog = Some(fog);
+        self
+    }
+
+    pub fn reflection_depth(mut self, depth: usize) -> Self {
+        self.world.reflection_depth = depth;
+        self
+    }
+
+    pub fn reflection_samples(mut self, samples: usize) -> Self {
+        self.world.reflection_samples = samples;
+        self
+    }
+
+    pub fn build(self) -> World {
+        self.world
+    }
+
+    /// Gives the material the id of any other material with the same definition.
+    fn next_material(&mut self, mut material: Material) -> Material {
+        self.material_ids.assign(&mut material);
+        material
+    }
+}
+
 pub trait Entity: Intersectable + Texture {
     fn material(&self) -> &Material;
+
+    /// Normal at `contact`, a point on the entity at rest as [`Intersection::new`] passes it,
+    /// before [`Entity::offset`] moves it.
     fn surface_normal(&self, contact: Vec3) -> Vec3;
+
+    /// How far the entity has moved from its configured position at `time` within the shutter.
+    fn offset(&self, _time: f32) -> Vec3 {
+        Vec3::zero()
+    }
+
+    /// The config entry that loads this entity, used when saving scenes. Entities that
+    /// cannot be written to a config return `None`.
+    fn to_config(&self) -> Option<Value> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ultraviolet::Vec3;
+
+    use crate::color::Color;
+    use crate::model::mesh::MeshData;
+    use crate::model::plane::Plane;
+    use crate::model::sphere::Sphere;
+    use crate::model::Material;
+    use crate::ray::{Intersectable, Ray};
+
+    use super::{Fog, World};
+
+    fn sphere(center: Vec3, radius: f32) -> Box<Sphere> {
+        Box::new(Sphere {
+            center,
+            radius,
+            ..Sphere::default()
+        })
+    }
+
+    #[test]
+    fn nearest_hit_is_measured_from_ray_origin() {
+        let mut world = World::new();
+        world.entities.push(sphere(Vec3::new(0.0, 0.0, -5.0), 0.5));
+        world.entities.push(sphere(Vec3::new(0.0, 0.0, 5.0), 0.5));
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 10.0), -Vec3::unit_z());
+        let intersection = world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+        assert!((intersection.t - 4.5).abs() < 1e-4);
+        assert!((intersection.hit.z - 5.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn intersection_respects_range() {
+        let mut world = World::new();
+        world.entities.push(sphere(Vec3::new(0.0, 0.0, -5.0), 0.5));
+
+        let ray = Ray::new(Vec3::zero(), -Vec3::unit_z());
+
+        assert!(world.intersect(&ray, 0.0..4.0).is_none());
+        assert!(world.intersect(&ray, 0.0..5.0).is_some());
+    }
+
+    #[test]
+    fn ray_inside_sphere_hits_far_side() {
+        let mut world = World::new();
+        world.entities.push(sphere(Vec3::new(0.0, 0.0, 20.0), 1.0));
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 20.0), Vec3::unit_z());
+        let intersection = world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+
+        assert!((intersection.t - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reflection_picks_nearest_surface() {
+        let mut world = World::new();
+        world.entities.push(Box::new(Plane {
+            position: Vec3::new(10.0, 1.0, 10.0),
+            normal: Vec3::unit_y(),
+            ..Plane::default()
+        }));
+        world
+            .entities
+            .push(sphere(Vec3::new(10.0, -2.0, 10.0), 0.5));
+        world
+            .entities
+            .push(sphere(Vec3::new(10.0, -20.0, 10.0), 0.5));
+
+        let ray = Ray::new(Vec3::new(10.0, -5.0, 10.0), Vec3::unit_y());
+        let mirror = world.intersect(&ray, Ray::UNBOUNDED).unwrap();
+        assert!((mirror.hit.y + 2.5).abs() < 1e-4);
+
+        let floor = Ray::new(Vec3::new(10.0, 0.0, 10.0), Vec3::unit_y());
+        let floor_hit = world.intersect(&floor, Ray::UNBOUNDED).unwrap();
+        let reflected = Ray::reflect(&floor_hit);
+        let bounce = world.intersect(&reflected, Ray::UNBOUNDED).unwrap();
+
+        assert!((bounce.hit.y + 1.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fog_fades_distant_objects_to_fog_color() {
+        let fog = Fog {
+            color: Color::new(0.5, 0.5, 0.5),
+            density: 0.5,
+        };
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        assert_eq!(fog.apply(white, 0.0), white);
+        assert_eq!(fog.apply(white, f32::INFINITY), fog.color);
+    }
+
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_ray_time() {
+        let mut world = World::new();
+        world.entities.push(Box::new(Sphere {
+            center: Vec3::new(0.0, 0.0, -5.0),
+            radius: 0.5,
+            motion: Vec3::new(2.0, 0.0, 0.0),
+            ..Sphere::default()
+        }));
+
+        let ray = Ray::new(Vec3::new(2.0, 0.0, 0.0), -Vec3::unit_z());
+        assert!(world.intersect(&ray, Ray::UNBOUNDED).is_none());
+
+        let hit = world
+            .intersect(&ray.with_time(1.0), Ray::UNBOUNDED)
+            .unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-5);
+        assert!((hit.contact.normal - Vec3::unit_z()).mag() < 1e-5);
+    }
+
+    #[test]
+    fn invalid_meshes_are_errors() {
+        let data = MeshData {
+            positions: vec![Vec3::zero(), Vec3::unit_x(), Vec3::unit_y()],
+            triangles: vec![[0, 1, 3]],
+            ..MeshData::default()
+        };
+
+        let error = World::builder()
+            .mesh(data, Material::default())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "vertex 3 is out of range for 3 vertices");
+    }
 }
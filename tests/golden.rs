@@ -0,0 +1,191 @@
+//! Renders the scenes in `assets/` and `tests/scenes/` at a low resolution and compares them
+//! with the reference images in `tests/golden/`.
+//!
+//! A render fails when its root mean square error against the reference exceeds
+//! [`TOLERANCE`], and is then written next to an image of the difference in the target
+//! directory. Run with `UPDATE_GOLDEN=1` to render the references again after an intended
+//! change.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use raytracer::serialize::parse_config;
+use raytracer::{import, render, RenderSettings, Scene};
+
+/// Width of every render, with the height following the camera's aspect ratio.
+const WIDTH: u32 = 64;
+const SAMPLES: u32 = 2;
+/// Largest root mean square error allowed, with channels between `0.0` and `1.0`.
+const TOLERANCE: f64 = 0.01;
+/// Brightens differences in the diff image so that small ones are still visible.
+const DIFF_GAIN: f64 = 4.0;
+
+fn load(path: &Path) -> Scene {
+    let scene = match import::open(path) {
+        Some(import) => import.map(|import| import.scene),
+        None => parse_config(path),
+    };
+
+    scene.unwrap_or_else(|error| panic!("loading {}: {error:?}", path.display()))
+}
+
+fn render_small(scene: &Scene) -> RgbaImage {
+    let height = (WIDTH as f32 * scene.height() as f32 / scene.width() as f32).round() as u32;
+    let settings = RenderSettings {
+        samples: SAMPLES,
+        resolution: Some((WIDTH, height.max(1))),
+        ..RenderSettings::default()
+    };
+
+    render(scene, &settings)
+}
+
+/// Root mean square error over the color channels, ignoring alpha.
+fn rmse(actual: &RgbaImage, expected: &RgbaImage) -> f64 {
+    let squares: f64 = actual
+        .pixels()
+        .zip(expected.pixels())
+        .flat_map(|(a, b)| (0..3).map(move |channel| (a[channel], b[channel])))
+        .map(|(a, b)| (a as f64 - b as f64) / 255.0)
+        .map(|error| error * error)
+        .sum();
+
+    (squares / (actual.len() / 4 * 3) as f64).sqrt()
+}
+
+fn diff(actual: &RgbaImage, expected: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let (a, b) = (actual.get_pixel(x, y), expected.get_pixel(x, y));
+        let channel = |c: usize| (a[c].abs_diff(b[c]) as f64 * DIFF_GAIN).min(255.0) as u8;
+        Rgba([channel(0), channel(1), channel(2), 255])
+    })
+}
+
+fn golden(path: &str) {
+    let path = Path::new(path);
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let reference = Path::new("tests/golden").join(format!("{name}.png"));
+    let actual = render_small(&load(path));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&reference).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&reference) {
+        Ok(expected) => expected.to_rgba8(),
+        Err(error) => panic!(
+            "reading {}: {error}; run with UPDATE_GOLDEN=1 to create it",
+            reference.display()
+        ),
+    };
+
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "{} renders at a different size than its reference",
+        path.display()
+    );
+
+    let error = rmse(&actual, &expected);
+    if error > TOLERANCE {
+        let out = output_dir();
+        let rendered = out.join(format!("{name}.png"));
+        let difference = out.join(format!("{name}.diff.png"));
+        actual.save(&rendered).unwrap();
+        diff(&actual, &expected).save(&difference).unwrap();
+
+        panic!(
+            "{} differs from {} by {error:.4} RMSE (tolerance {TOLERANCE}), see {} and {}",
+            path.display(),
+            reference.display(),
+            rendered.display(),
+            difference.display()
+        );
+    }
+}
+
+fn output_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn config() {
+    golden("assets/config.yaml");
+}
+
+#[test]
+fn blackout() {
+    golden("assets/blackout.yaml");
+}
+
+#[test]
+fn extreme_reflection() {
+    golden("assets/extreme-reflection.yaml");
+}
+
+#[test]
+fn fog() {
+    golden("assets/fog.yaml");
+}
+
+#[test]
+fn library() {
+    golden("assets/library.yaml");
+}
+
+#[test]
+fn meshes() {
+    golden("assets/meshes.yaml");
+}
+
+#[test]
+fn smoke() {
+    golden("assets/smoke.yaml");
+}
+
+#[test]
+fn spheres() {
+    golden("assets/spheres.yaml");
+}
+
+#[test]
+fn turntable() {
+    golden("assets/turntable.yaml");
+}
+
+#[test]
+fn pbrt_scene() {
+    golden("assets/scene.pbrt");
+}
+
+#[test]
+fn diffuse() {
+    golden("tests/scenes/diffuse.yaml");
+}
+
+#[test]
+fn reflection() {
+    golden("tests/scenes/reflection.yaml");
+}
+
+#[test]
+fn spot() {
+    golden("tests/scenes/spot.yaml");
+}
+
+#[test]
+fn rmse_measures_channel_differences() {
+    let black = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+    let mut grey = black.clone();
+    assert_eq!(rmse(&black, &grey), 0.0);
+
+    grey.put_pixel(0, 0, Rgba([255, 255, 255, 0]));
+    assert!((rmse(&black, &grey) - 0.5).abs() < 1e-9);
+    assert_eq!(
+        diff(&black, &grey).get_pixel(0, 0),
+        &Rgba([255, 255, 255, 255])
+    );
+}